@@ -0,0 +1,10 @@
+#![no_main]
+
+use benq_control::Command;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|s: &str| {
+  if let Ok(command) = s.parse::<Command>() {
+    assert_eq!(command.to_string().parse::<Command>().unwrap(), command);
+  }
+});
@@ -0,0 +1,22 @@
+#![no_main]
+
+use benq_control::protocol::{self, EchoMode};
+use libfuzzer_sys::fuzz_target;
+
+// the first byte picks the length of the echo, which is taken from the start
+// of the input; the rest is what the projector "sent"
+fuzz_target!(|data: &[u8]| {
+  let (echo, buf) = match data.split_first() {
+    Some((&n, rest)) => rest.split_at((n as usize).min(rest.len())),
+    None => return
+  };
+
+  for mode in [EchoMode::Auto, EchoMode::Always, EchoMode::Never] {
+    let _ = protocol::parse_response(buf, echo, mode);
+    let _ = protocol::finish_response(buf, echo, mode);
+  }
+
+  for frame in protocol::frames(buf) {
+    assert_eq!(&buf[frame.start + 1..frame.end - 1], frame.body);
+  }
+});
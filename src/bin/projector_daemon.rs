@@ -1,8 +1,14 @@
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, UNIX_EPOCH};
 
 use astro_dnssd::{txt::TXTRecord, register::DNSServiceBuilder};
-use benq_control::{Command, ProjectorControl};
+use benq_control::{
+  Command, CommandResult, Error, ProjectorControl, QueuePolicy, DEFAULT_QUEUE_CAPACITY
+};
+use benq_control::detect::BaudRate;
+use benq_control::discovery::{self, PortSelector};
+use benq_control::protocol::{EchoMode, LineEnding, PromptMode, ProtocolOptions};
+use benq_control::sequence::Sequence;
 use color_eyre::eyre::{Result, Context, ContextCompat, eyre};
 use futures::try_join;
 use log::*;
@@ -15,22 +21,52 @@ use url::Url;
 #[derive(Debug, Clone, StructOpt)]
 #[structopt(name = "projector-tool")]
 struct Options {
-  /// projector serial port device path
+  /// projector serial port: a device path, a USB adapter as
+  /// `usb:<vid>[:<pid>[:<serial>]]` (hex IDs), or `auto` to probe all ports
   #[structopt(
     long, short,
     default_value = "/dev/ttyUSB0",
     global = true,
     env = "PROJECTOR_DEVICE"
   )]
-  device: String,
+  device: PortSelector,
 
+  /// serial baud rate, or `auto` to detect it
   #[structopt(
     long, short,
     default_value = "115200",
     global = true,
     env = "PROJECTOR_BAUD_RATE"
   )]
-  baud_rate: u32,
+  baud_rate: BaudRate,
+
+  /// whether the projector echoes commands: auto, always or never
+  #[structopt(
+    long,
+    default_value = "auto",
+    global = true,
+    env = "PROJECTOR_ECHO"
+  )]
+  echo: EchoMode,
+
+  /// line ending to send after commands: cr or crlf
+  #[structopt(
+    long,
+    default_value = "cr",
+    global = true,
+    env = "PROJECTOR_LINE_ENDING"
+  )]
+  line_ending: LineEnding,
+
+  /// whether the projector must print a prompt before each command: required
+  /// or optional
+  #[structopt(
+    long,
+    default_value = "required",
+    global = true,
+    env = "PROJECTOR_PROMPT"
+  )]
+  prompt: PromptMode,
 
   /// port and protocol to listen on
   #[structopt(
@@ -58,6 +94,16 @@ struct Options {
   unique_id: Option<String>
 }
 
+impl Options {
+  fn protocol_options(&self) -> ProtocolOptions {
+    ProtocolOptions {
+      echo: self.echo,
+      line_ending: self.line_ending,
+      prompt: self.prompt,
+    }
+  }
+}
+
 #[derive(Debug, Serialize)]
 struct ProjectorStatus {
   state: ProjectorState,
@@ -88,17 +134,31 @@ impl ProjectorState {
 
 type WrappedProjectorStatus = Arc<RwLock<ProjectorStatus>>;
 
+/// Builds the response to a command, as `{"response": ...}` or
+/// `{"error": "..."}`.
+fn command_reply(result: CommandResult) -> (u16, serde_json::Value) {
+  match result {
+    Ok(response) => (200, json!({"response": response})),
+    Err(e) => (500, error_body(e))
+  }
+}
+
+/// Builds an error body in the same format as [`command_reply`].
+fn error_body(e: Error) -> serde_json::Value {
+  json!({"error": e.to_string()})
+}
+
 async fn fetch_status(
   controller: &ProjectorControl,
   prev_power_state: bool,
   unique_id: impl Into<String>
 ) -> Result<ProjectorStatus> {
-  let model = controller.submit_command("modelname")
+  let model = controller.submit_background("modelname")
     .await?
     .map(|m| m.trim_start_matches("MODELNAME=").to_string())
     .unwrap_or(String::from("Unknown"));
 
-  let power = controller.submit_command("pow").await?;
+  let power = controller.submit_background("pow").await?;
   if let Some("POW=ON") = power.as_deref() {
     if !prev_power_state {
       // looks like the projector turned on, send a sleep command to block
@@ -109,9 +169,9 @@ async fn fetch_status(
     }
 
     let (source, volume, mute) = try_join!(
-      controller.submit_command("sour"),
-      controller.submit_command("vol"),
-      controller.submit_command("mute"),
+      controller.submit_background("sour"),
+      controller.submit_background("vol"),
+      controller.submit_background("mute"),
     )?;
 
     let source = source
@@ -186,12 +246,26 @@ async fn update_state(controller: &ProjectorControl, status: &WrappedProjectorSt
 }
 
 async fn update_state_task(controller: &ProjectorControl, state: WrappedProjectorStatus) {
+  match controller.detect_profile().await {
+    Ok(profile) => info!("using profile for {}", profile.model),
+    Err(e) => warn!("could not detect projector model, using generic profile: {}", e)
+  }
+
   let mut interval = tokio::time::interval(Duration::from_secs(60));
   loop {
     interval.tick().await;
 
     if let Err(e) = update_state(controller, &state).await {
       warn!("state update failed: {:?}", e);
+
+      for entry in controller.journal().iter().rev().take(5).rev() {
+        debug!("{}", entry);
+      }
+    }
+
+    let queue = controller.queue_status();
+    if queue.depth >= queue.capacity {
+      warn!("command queue is full ({} commands)", queue.depth);
     }
   }
 }
@@ -199,7 +273,7 @@ async fn update_state_task(controller: &ProjectorControl, state: WrappedProjecto
 #[derive(Clone)]
 struct State {
   projector_status: WrappedProjectorStatus,
-  controller: Arc<ProjectorControl>,
+  controller: ProjectorControl,
 }
 
 fn register_dnssd(listen: &str, name: &str, unique_id: &str) -> Result<()> {
@@ -240,7 +314,7 @@ async fn main() -> Result<()> {
 
   let opts = Options::from_args();
 
-  let unique_id = if let Some(unique_id) = opts.unique_id {
+  let unique_id = if let Some(unique_id) = &opts.unique_id {
     unique_id.clone()
   } else {
     match mac_address::get_mac_address() {
@@ -268,10 +342,30 @@ async fn main() -> Result<()> {
     }
   });
 
-  let serial_port = serialport::new(&opts.device, opts.baud_rate)
-    .timeout(Duration::from_millis(100))
-    .open()?;
-  let controller = Arc::new(ProjectorControl::new(serial_port));
+  let serial_port = discovery::open(
+    &opts.device,
+    opts.baud_rate,
+    Duration::from_millis(100),
+    &opts.protocol_options()
+  )?;
+
+  // reopen via the selector so e.g. usb:... finds the adapter again even if it
+  // comes back under a different path
+  let reopen_device = opts.device.clone();
+  let reopen_baud_rate = opts.baud_rate;
+  let reopen_options = opts.protocol_options();
+  let controller = ProjectorControl::builder()
+    .protocol(opts.protocol_options())
+    // status polling is submitted as background work, so it gives way to
+    // client requests if the queue backs up
+    .queue(DEFAULT_QUEUE_CAPACITY, QueuePolicy::DropOldestBackground)
+    .reconnect(move || discovery::open(
+      &reopen_device,
+      reopen_baud_rate,
+      Duration::from_millis(100),
+      &reopen_options
+    ))
+    .build(serial_port);
   let projector_status = Arc::new(RwLock::new(ProjectorStatus {
     model: "Unknown".to_string(),
     state: ProjectorState::Invalid,
@@ -279,7 +373,7 @@ async fn main() -> Result<()> {
   }));
 
   // spawn a task to continuously refresh the projector's status
-  let refresh_controller = Arc::clone(&controller);
+  let refresh_controller = controller.clone();
   let refresh_status = Arc::clone(&projector_status);
   task::spawn(async move {
     update_state_task(&refresh_controller, refresh_status).await;
@@ -287,7 +381,7 @@ async fn main() -> Result<()> {
 
   let state = State {
     projector_status: Arc::clone(&projector_status),
-    controller: Arc::clone(&controller),
+    controller: controller.clone(),
   };
 
   let mut app = tide::with_state(state);
@@ -297,14 +391,83 @@ async fn main() -> Result<()> {
     Ok(Body::from_json(&*projector_status)?)
   });
 
+  app.at("/connection").get(|req: Request<State>| async move {
+    let state = req.state().controller.connection_state();
+
+    Body::from_json(&json!({"state": state.to_string()}))
+  });
+
+  app.at("/journal").get(|req: Request<State>| async move {
+    let entries = req.state().controller.journal().iter().map(|entry| {
+      let timestamp = entry.timestamp
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+
+      let (response, error) = match &entry.result {
+        Ok(response) => (response.clone(), None),
+        Err(e) => (None, Some(e.clone())),
+      };
+
+      json!({
+        "timestamp_ms": timestamp.as_millis() as u64,
+        "command": entry.command,
+        "written": entry.written.escape_ascii().to_string(),
+        "read": entry.read.escape_ascii().to_string(),
+        "response": response,
+        "error": error,
+        "duration_ms": entry.duration.as_millis() as u64,
+        "delay_ms": entry.delay.as_millis() as u64,
+      })
+    }).collect::<Vec<_>>();
+
+    Body::from_json(&entries)
+  });
+
+  app.at("/queue").get(|req: Request<State>| async move {
+    Body::from_json(&req.state().controller.queue_status())
+  });
+
+  app.at("/maintenance").get(|req: Request<State>| async move {
+    let (code, body) = match req.state().controller.maintenance().await {
+      Ok(status) => (200, json!(status)),
+      Err(e) => (500, error_body(e))
+    };
+
+    Ok(Response::builder(code).body(body).build())
+  });
+
+  // runs an arbitrary command given in the body, e.g. `pow=?` or `bri=50`
+  app.at("/exec").post(|mut req: Request<State>| async move {
+    let body = req.body_string().await?;
+
+    let (code, body) = match body.parse::<Command>() {
+      Ok(command) => command_reply(req.state().controller.submit_command(command).await),
+      Err(e) => (400, error_body(e))
+    };
+
+    Ok(Response::builder(code).body(body).build())
+  });
+
+  // runs a sequence given as JSON in the body, see benq_control::sequence
+  app.at("/sequence").post(|mut req: Request<State>| async move {
+    let body = req.body_string().await?;
+
+    let (code, body) = match serde_json::from_str::<Sequence>(&body) {
+      Ok(sequence) => {
+        let report = req.state().controller.run_sequence(&sequence).await;
+        let code = if report.is_ok() { 200 } else { 500 };
+        (code, json!(report))
+      },
+      Err(e) => (400, error_body(Error::InvalidValue(e.to_string())))
+    };
+
+    Ok(Response::builder(code).body(body).build())
+  });
+
   app.at("/power").get(|req: Request<State>| async move {
     let controller = &req.state().controller;
 
-    let (code, response) = match controller.submit_command("pow").await {
-      Ok(Some(response)) => (200, json!({"response": response})),
-      Ok(None) => (200, json!({"response": null})),
-      Err(e) => (500, json!({"error": format!("{}", e)}))
-    };
+    let (code, response) = command_reply(controller.submit_command("pow").await);
 
     Ok(
       Response::builder(code)
@@ -318,11 +481,7 @@ async fn main() -> Result<()> {
     let controller = &req.state().controller;
 
     let response = if power == "on" || power == "off" {
-      let (code, body) = match controller.submit_command(("pow", power.as_str())).await {
-        Ok(Some(response)) => (200, json!({"response": response})),
-        Ok(None) => (200, json!({"response": null})),
-        Err(e) => (500, json!({"error": e.to_string()}))
-      };
+      let (code, body) = command_reply(controller.submit_command(("pow", power.as_str())).await);
 
       // if successful, update the state directly - the processing thread will
       // be paused for quite a while but we can safely assume it's (turning) off
@@ -333,9 +492,8 @@ async fn main() -> Result<()> {
 
       Response::builder(code).body(body).build()
     } else {
-      Response::builder(400).body(json!({
-        "error": format!("invalid power state: {}", power)
-      })).build()
+      let e = Error::InvalidValue(format!("power state {}", power));
+      Response::builder(400).body(error_body(e)).build()
     };
 
     Ok(response)
@@ -346,11 +504,7 @@ async fn main() -> Result<()> {
     let controller = &req.state().controller;
 
     let response = if let "rgb" | "hdmi" | "hdmi2" = source.as_str() {
-      let (code, body) = match controller.submit_command(("sour", source)).await {
-        Ok(Some(response)) => (200, json!({"response": response})),
-        Ok(None) => (200, json!({"response": null})),
-        Err(e) => (500, json!({"error": e.to_string()}))
-      };
+      let (code, body) = command_reply(controller.submit_command(("sour", source)).await);
 
       // kick off a state update right away to reflect the new status
       if let Err(e) = update_state(controller, &req.state().projector_status).await {
@@ -359,9 +513,8 @@ async fn main() -> Result<()> {
 
       Response::builder(code).body(body).build()
     } else {
-      Response::builder(400).body(json!({
-        "error": format!("invalid source: {}", source)
-      })).build()
+      let e = Error::InvalidValue(format!("source {}", source));
+      Response::builder(400).body(error_body(e)).build()
     };
 
     Ok(response)
@@ -373,11 +526,7 @@ async fn main() -> Result<()> {
 
     let (code, body) = match volume.parse::<u8>() {
       Ok(v @ 0..=20) => {
-        let (code, body) = match controller.submit_command(("vol", v.to_string())).await {
-          Ok(Some(response)) => (200, json!({"response": response})),
-          Ok(None) => (200, json!({"response": null})),
-          Err(e) => (500, json!({"error": e.to_string()}))
-        };
+        let (code, body) = command_reply(controller.submit_command(("vol", v.to_string())).await);
 
         // kick off a state update right away to reflect the new status
         if let Err(e) = update_state(controller, &req.state().projector_status).await {
@@ -386,12 +535,8 @@ async fn main() -> Result<()> {
 
         (code, body)
       },
-      Ok(_) => (400, json!({
-        "error": format!("volume out of range: {}", volume)
-      })),
-      Err(_) => (400, json!({
-        "error": format!("invalid volume: {}", volume)
-      }))
+      Ok(_) => (400, error_body(Error::InvalidValue(format!("volume {} (must be 0-20)", volume)))),
+      Err(_) => (400, error_body(Error::InvalidValue(format!("volume {}", volume))))
     };
 
     Ok(Response::builder(code).body(body).build())
@@ -402,11 +547,7 @@ async fn main() -> Result<()> {
     let controller = &req.state().controller;
 
     let (code, body) = if let "on" | "off" = mute.as_str() {
-      let (code, body) = match controller.submit_command(("mute", mute)).await {
-        Ok(Some(response)) => (200, json!({"response": response})),
-        Ok(None) => (200, json!({"response": null})),
-        Err(e) => (500, json!({"error": e.to_string()}))
-      };
+      let (code, body) = command_reply(controller.submit_command(("mute", mute)).await);
 
       // kick off a state update right away to reflect the new status
       if let Err(e) = update_state(controller, &req.state().projector_status).await {
@@ -415,9 +556,7 @@ async fn main() -> Result<()> {
 
       (code, body)
     } else {
-      (400, json!({
-        "error": format!("invalid mute state: {}", mute)
-      }))
+      (400, error_body(Error::InvalidValue(format!("mute state {}", mute))))
     };
 
     Ok(Response::builder(code).body(body).build())
@@ -1,21 +1,32 @@
 
 use std::fmt;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 
-use benq_control::{ProjectorControl, Command};
+use benq_control::{
+  AspectRatio, ColorTemperature, Command, Gamma, KeystoneAxis, LampMode, Language, PictureLevel,
+  PictureMode, PowerProgress, PowerState, PowerWait, ProjectorPosition, RemoteKey, Snapshot,
+  Source, ThreeDMode
+};
+use benq_control::blocking::ProjectorControl;
+use benq_control::detect::{self, BaudRate};
+use benq_control::discovery::{self, PortSelector};
+use benq_control::protocol::{EchoMode, LineEnding, PromptMode, ProtocolOptions, Transport};
+use benq_control::sequence::{OnError, Sequence};
+use benq_control::transcript::Recorder;
 use color_eyre::eyre::{Result, Context, eyre};
 use log::*;
+use serialport::SerialPortType;
 use structopt::StructOpt;
+use structopt::clap::AppSettings;
+
+const PORT_TIMEOUT: Duration = Duration::from_millis(50);
 
 fn parse_command(s: &str) -> Result<Command> {
-  let tokens = s.splitn(2, '=').collect::<Vec<_>>();
-  match &tokens[..] {
-    // assume no '=' means get, since =? triggers shell expansion and requires
-    // single quoting
-    [lhs] | [lhs, "?"] => Ok(Command::Get(lhs.to_ascii_lowercase())),
-    [lhs, rhs] => Ok(Command::Set((lhs.to_ascii_lowercase(), rhs.to_ascii_lowercase()))),
-    _ => Err(eyre!("invalid command string: {}", s))
-  }
+  // a bare key means get, since =? triggers shell expansion and requires
+  // single quoting
+  Ok(s.parse::<Command>()?)
 }
 
 fn parse_volume(s: &str) -> Result<u8> {
@@ -32,8 +43,16 @@ fn parse_volume(s: &str) -> Result<u8> {
 #[derive(Debug, Clone, StructOpt)]
 #[structopt(rename_all = "kebab-case")]
 enum PowerAction {
-  On,
-  Off,
+  On {
+    /// wait until the projector has warmed up and accepts commands
+    #[structopt(long, short)]
+    wait: bool
+  },
+  Off {
+    /// wait until the projector has cooled down and reports that it is off
+    #[structopt(long, short)]
+    wait: bool
+  },
   Status
 }
 
@@ -47,14 +66,14 @@ enum SourceAction {
   Status
 }
 
-impl fmt::Display for SourceAction {
-  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-    write!(f, "{}", match self {
-      SourceAction::HDMI => "hdmi",
-      SourceAction::HDMI2 => "hdmi2",
-      SourceAction::RGB => "rgb",
-      SourceAction::Status => "status"
-  })
+impl SourceAction {
+  fn source(&self) -> Option<Source> {
+    match self {
+      SourceAction::HDMI => Some(Source::Hdmi),
+      SourceAction::HDMI2 => Some(Source::Hdmi2),
+      SourceAction::RGB => Some(Source::Rgb),
+      SourceAction::Status => None
+    }
   }
 }
 
@@ -78,6 +97,183 @@ enum MuteAction {
   Status
 }
 
+#[derive(Debug, Clone, StructOpt)]
+#[structopt(rename_all = "kebab-case")]
+enum BlankAction {
+  On,
+  Off,
+  Status
+}
+
+#[derive(Debug, Clone, StructOpt)]
+#[structopt(rename_all = "kebab-case")]
+enum FreezeAction {
+  On,
+  Off,
+  Status
+}
+
+#[derive(Debug, Clone, StructOpt)]
+#[structopt(rename_all = "kebab-case")]
+enum ThreeDAction {
+  /// Selects a 3D format: off, auto, fs, tb, sbs or fp
+  Set {
+    mode: ThreeDMode
+  },
+  Status
+}
+
+#[derive(Debug, Clone, StructOpt)]
+#[structopt(rename_all = "kebab-case")]
+enum LevelAction {
+  Up,
+  Down,
+  Set {
+    value: u8
+  },
+  Status
+}
+
+#[derive(Debug, Clone, StructOpt)]
+#[structopt(rename_all = "kebab-case")]
+enum PictureAction {
+  /// Sets or queries the picture mode, e.g. `cine`, `game` or `user1`
+  #[structopt(aliases = &["appmod"])]
+  Mode {
+    value: Option<PictureMode>
+  },
+
+  #[structopt(aliases = &["bri"])]
+  Brightness(LevelAction),
+
+  #[structopt(aliases = &["con"])]
+  Contrast(LevelAction),
+
+  #[structopt(aliases = &["colour"])]
+  Color(LevelAction),
+
+  #[structopt(aliases = &["sharp"])]
+  Sharpness(LevelAction),
+
+  /// Sets or queries the colour temperature, e.g. `warm` or `normal`
+  #[structopt(aliases = &["ct", "colour-temp"])]
+  ColorTemp {
+    value: Option<ColorTemperature>
+  },
+
+  /// Sets or queries the gamma, e.g. `2.2`
+  Gamma {
+    value: Option<Gamma>
+  },
+
+  /// Sets or queries the aspect ratio, e.g. `auto` or `16:9`
+  #[structopt(aliases = &["asp"])]
+  Aspect {
+    value: Option<AspectRatio>
+  },
+
+  /// Queries all picture settings
+  Status
+}
+
+#[derive(Debug, Clone, StructOpt)]
+#[structopt(rename_all = "kebab-case")]
+enum LampAction {
+  /// Sets or queries the lamp mode, e.g. `normal`, `eco` or `smarteco`
+  #[structopt(aliases = &["lampm"])]
+  Mode {
+    value: Option<LampMode>
+  },
+
+  /// Queries the hours on the lamp
+  #[structopt(aliases = &["ltim"])]
+  Hours,
+
+  /// Queries the lamp mode and every lamp and filter counter
+  Status
+}
+
+#[derive(Debug, Clone, StructOpt)]
+#[structopt(rename_all = "kebab-case")]
+enum KeystoneAction {
+  Up,
+  Down,
+  #[structopt(setting = AppSettings::AllowNegativeNumbers)]
+  Set {
+    value: i8
+  },
+  Status
+}
+
+#[derive(Debug, Clone, StructOpt)]
+#[structopt(rename_all = "kebab-case")]
+enum InstallAction {
+  /// Sets or queries how the projector is mounted: ft (front table), re (rear
+  /// table), rc (rear ceiling) or fc (front ceiling)
+  #[structopt(aliases = &["pp"])]
+  Position {
+    value: Option<ProjectorPosition>
+  },
+
+  /// Adjusts or queries keystone correction on an axis, `vertical` or
+  /// `horizontal`
+  Keystone {
+    axis: KeystoneAxis,
+
+    #[structopt(subcommand)]
+    action: KeystoneAction
+  },
+
+  /// Sets or queries the menu language, e.g. `english`
+  #[structopt(aliases = &["lang"])]
+  Language {
+    value: Option<Language>
+  },
+
+  /// Queries all installation settings
+  Status
+}
+
+#[derive(Debug, Clone, StructOpt)]
+#[structopt(rename_all = "kebab-case")]
+enum SnapshotAction {
+  /// Reads every setting the projector model supports and writes them to a
+  /// file, or to stdout as JSON
+  Save {
+    path: Option<PathBuf>
+  },
+
+  /// Applies the settings in a snapshot file, reporting any that fail
+  Restore {
+    path: PathBuf
+  },
+
+  /// Compares a snapshot file with the projector's current settings, or with
+  /// a second snapshot file
+  Diff {
+    path: PathBuf,
+    other: Option<PathBuf>
+  }
+}
+
+#[derive(Debug, Clone, StructOpt)]
+struct RunAction {
+  /// sequence file in JSON, see the `benq_control::sequence` docs
+  path: PathBuf,
+
+  /// carry on after a step fails, whatever the sequence says
+  #[structopt(long, short)]
+  keep_going: bool
+}
+
+#[derive(Debug, Clone, StructOpt)]
+struct PressAction {
+  /// keys to press in order: menu, exit, up, down, left, right, enter, back or
+  /// auto
+  #[structopt(required = true)]
+  keys: Vec<RemoteKey>
+}
+
 #[derive(Debug, Clone, StructOpt)]
 struct ExecAction {
   #[structopt(parse(try_from_str = parse_command))]
@@ -106,123 +302,574 @@ enum Action {
   #[structopt(aliases = &["m"])]
   Mute(MuteAction),
 
+  /// Blanks the screen or queries whether it is blank. Returns an error if the
+  /// projector is not currently powered on.
+  #[structopt(aliases = &["b"])]
+  Blank(BlankAction),
+
+  /// Freezes the picture or queries whether it is frozen. Returns an error if
+  /// the projector is not currently powered on.
+  #[structopt(aliases = &["f"])]
+  Freeze(FreezeAction),
+
+  /// Sets or queries the 3D format on models that support 3D. Returns an
+  /// error if the projector is not currently powered on.
+  #[structopt(name = "3d", aliases = &["three-d"])]
+  ThreeD(ThreeDAction),
+
+  /// Sets or queries picture settings. Values are checked against the
+  /// projector model's profile where one is known. Returns an error if the
+  /// projector is not currently powered on.
+  #[structopt(aliases = &["pic"])]
+  Picture(PictureAction),
+
+  /// Sets or queries the lamp mode and reports lamp and filter hours
+  #[structopt(aliases = &["l"])]
+  Lamp(LampAction),
+
+  /// Sets or queries installation settings: projection position, keystone and
+  /// menu language. Returns an error if the projector is not currently powered
+  /// on.
+  Install(InstallAction),
+
+  /// Saves, restores or compares projector settings. Snapshot files are JSON,
+  /// or TOML if the name ends in `.toml`. Returns an error if the projector is
+  /// not currently powered on.
+  Snapshot(SnapshotAction),
+
+  /// Runs a sequence of commands, waits and conditional steps from a file,
+  /// printing the outcome of each step
+  Run(RunAction),
+
+  /// Presses buttons on the remote control, e.g. `press menu down enter`
+  Press(PressAction),
+
   /// Executes an arbitrary command. Refer to the documentation for a full list
   /// of commands.
   ///
   /// Note that this command accepts slightly different syntax: `key=value` to
-  /// set an option and `key=?` or just `key` to query a value.
+  /// set an option, `key=?` or just `key` to query a value, and `key!` to send
+  /// a bare action.
   #[structopt(aliases = &["e"])]
   Exec(ExecAction),
+
+  /// Detects the projector's baud rate and line settings by trying each
+  /// supported combination in turn. This may take several seconds.
+  Detect,
+
+  /// Lists available serial ports along with their USB adapter identity, if
+  /// known. The `usb:...` column may be passed to `--device`.
+  Ports,
 }
 
 #[derive(Debug, Clone, StructOpt)]
 #[structopt(name = "projector-tool")]
 struct Options {
-  /// projector serial port device path
+  /// projector serial port: a device path, a USB adapter as
+  /// `usb:<vid>[:<pid>[:<serial>]]` (hex IDs), or `auto` to probe all ports
   #[structopt(
     long, short,
     default_value = "/dev/ttyUSB0",
     global = true,
     env = "PROJECTOR_DEVICE"
   )]
-  device: String,
+  device: PortSelector,
 
+  /// serial baud rate, or `auto` to detect it
   #[structopt(
     long, short,
     default_value = "115200",
     global = true,
     env = "PROJECTOR_BAUD_RATE"
   )]
-  baud_rate: u32,
+  baud_rate: BaudRate,
+
+  /// whether the projector echoes commands: auto, always or never
+  #[structopt(
+    long,
+    default_value = "auto",
+    global = true,
+    env = "PROJECTOR_ECHO"
+  )]
+  echo: EchoMode,
+
+  /// line ending to send after commands: cr or crlf
+  #[structopt(
+    long,
+    default_value = "cr",
+    global = true,
+    env = "PROJECTOR_LINE_ENDING"
+  )]
+  line_ending: LineEnding,
+
+  /// whether the projector must print a prompt before each command: required
+  /// or optional
+  #[structopt(
+    long,
+    default_value = "required",
+    global = true,
+    env = "PROJECTOR_PROMPT"
+  )]
+  prompt: PromptMode,
+
+  /// print the journal of raw exchanges with the projector to stderr if the
+  /// command fails
+  #[structopt(long, global = true, env = "PROJECTOR_JOURNAL")]
+  journal: bool,
+
+  /// record a transcript of the session to this file, e.g. to reproduce a bug
+  /// without the projector
+  #[structopt(long, global = true, env = "PROJECTOR_RECORD")]
+  record: Option<PathBuf>,
 
   #[structopt(subcommand)]
   action: Action
 }
 
-async fn handle_power(
+impl Options {
+  fn protocol_options(&self) -> ProtocolOptions {
+    ProtocolOptions {
+      echo: self.echo,
+      line_ending: self.line_ending,
+      prompt: self.prompt,
+    }
+  }
+}
+
+/// Prints a setting the way the projector reports it, e.g. `POW=ON`, as the
+/// power, source, volume and mute commands always have.
+fn print_setting(key: &str, value: impl fmt::Display) {
+  println!("{}={}", key.to_uppercase(), value.to_string().to_uppercase());
+}
+
+fn handle_power(
   _opts: &Options,
   action: &PowerAction,
   controller: ProjectorControl
 ) -> Result<()> {
-  let res = match action {
-    PowerAction::On => controller.submit_command(("pow", "on")),
-    PowerAction::Off => controller.submit_command(("pow", "off")),
-    PowerAction::Status => controller.submit_command("pow")
-  }.await?;
-
-  debug!("power response: {:?}", res);
-  if let Some(r) = res {
-    println!("{}", r);
+  match action {
+    PowerAction::On { wait: true } => wait_for_power(PowerState::On, &controller)?,
+    PowerAction::Off { wait: true } => wait_for_power(PowerState::Off, &controller)?,
+    PowerAction::On { wait: false } => {
+      controller.set_power(PowerState::On)?;
+      print_setting("pow", PowerState::On);
+    },
+    PowerAction::Off { wait: false } => {
+      controller.set_power(PowerState::Off)?;
+      print_setting("pow", PowerState::Off);
+    },
+    PowerAction::Status => print_setting("pow", controller.power()?),
   }
 
   Ok(())
 }
 
-async fn handle_source(
+fn wait_for_power(target: PowerState, controller: &ProjectorControl) -> Result<()> {
+  let progress = |p: PowerProgress| match p {
+    PowerProgress::Sent if target == PowerState::On => eprint!("warming up..."),
+    PowerProgress::Sent => eprint!("cooling down..."),
+    PowerProgress::Waiting { .. } => eprint!("."),
+    PowerProgress::Ready { elapsed } => eprintln!(" ready ({}s)", elapsed.as_secs()),
+  };
+
+  let result = match target {
+    PowerState::On => controller.power_on_and_wait(PowerWait::default(), progress),
+    PowerState::Off => controller.power_off_and_wait(PowerWait::default(), progress),
+  };
+
+  if result.is_err() {
+    eprintln!(" failed");
+  }
+
+  Ok(result?)
+}
+
+fn handle_source(
   _opts: &Options,
   action: &SourceAction,
   controller: ProjectorControl
 ) -> Result<()> {
-  let res = match action {
-    SourceAction::Status => controller.submit_command("sour"),
-    source => controller.submit_command(("sour", source.to_string()))
-  }.await?;
-
-  debug!("source response: {:?}", res);
-  if let Some(r) = res {
-    println!("{}", r);
+  match action.source() {
+    Some(source) => {
+      controller.set_source(&source)?;
+      print_setting("sour", source);
+    },
+    None => print_setting("sour", controller.source()?),
   }
 
   Ok(())
 }
 
-async fn handle_volume(
+fn handle_volume(
   _opts: &Options,
   action: &VolumeAction,
   controller: ProjectorControl
 ) -> Result<()> {
-  let res = match action {
-    VolumeAction::Status => controller.submit_command("vol"),
-    VolumeAction::Up => controller.submit_command(("vol", "+")),
-    VolumeAction::Down => controller.submit_command(("vol", "-")),
-    VolumeAction::Set { value } => controller.submit_command(("vol", value.to_string())),
-  }.await?;
-
-  debug!("volume response: {:?}", res);
-  if let Some(r) = res {
-    println!("{}", r);
+  match action {
+    VolumeAction::Up => {
+      controller.volume_up()?;
+      print_setting("vol", "+");
+    },
+    VolumeAction::Down => {
+      controller.volume_down()?;
+      print_setting("vol", "-");
+    },
+    VolumeAction::Set { value } => {
+      controller.set_volume(*value)?;
+      print_setting("vol", value);
+    },
+    VolumeAction::Status => print_setting("vol", controller.volume()?),
   }
 
   Ok(())
 }
 
-async fn handle_mute(
+fn handle_mute(
   _opts: &Options,
   action: &MuteAction,
   controller: ProjectorControl
 ) -> Result<()> {
-  let res = match action {
-    MuteAction::Status => controller.submit_command("mute"),
-    MuteAction::On => controller.submit_command(("mute", "on")),
-    MuteAction::Off => controller.submit_command(("mute", "off")),
-  }.await?;
+  let muted = match action {
+    MuteAction::On => {
+      controller.set_muted(true)?;
+      true
+    },
+    MuteAction::Off => {
+      controller.set_muted(false)?;
+      false
+    },
+    MuteAction::Status => controller.muted()?,
+  };
 
-  debug!("mute response: {:?}", res);
-  if let Some(r) = res {
-    println!("{}", r);
+  print_setting("mute", if muted { "on" } else { "off" });
+  Ok(())
+}
+
+fn handle_blank(
+  _opts: &Options,
+  action: &BlankAction,
+  controller: ProjectorControl
+) -> Result<()> {
+  match action {
+    BlankAction::On => controller.set_blank(true)?,
+    BlankAction::Off => controller.set_blank(false)?,
+    BlankAction::Status => println!("{}", if controller.blanked()? { "on" } else { "off" }),
+  }
+
+  Ok(())
+}
+
+fn handle_freeze(
+  _opts: &Options,
+  action: &FreezeAction,
+  controller: ProjectorControl
+) -> Result<()> {
+  match action {
+    FreezeAction::On => controller.set_freeze(true)?,
+    FreezeAction::Off => controller.set_freeze(false)?,
+    FreezeAction::Status => println!("{}", if controller.frozen()? { "on" } else { "off" }),
+  }
+
+  Ok(())
+}
+
+fn handle_three_d(
+  _opts: &Options,
+  action: &ThreeDAction,
+  controller: ProjectorControl
+) -> Result<()> {
+  match action {
+    ThreeDAction::Set { mode } => {
+      detect_profile(&controller);
+      controller.set_three_d_mode(mode)?
+    },
+    ThreeDAction::Status => println!("{}", controller.three_d_mode()?),
+  }
+
+  Ok(())
+}
+
+/// Switches to the projector's model profile so that values are validated
+/// before being sent.
+fn detect_profile(controller: &ProjectorControl) {
+  match controller.detect_profile() {
+    Ok(profile) => debug!("using profile for {}", profile.model),
+    Err(e) => warn!("could not detect model, values will not be validated: {}", e)
+  }
+}
+
+fn handle_picture_level(
+  level: PictureLevel,
+  action: &LevelAction,
+  controller: &ProjectorControl
+) -> Result<()> {
+  match action {
+    LevelAction::Up => controller.picture_level_up(level)?,
+    LevelAction::Down => controller.picture_level_down(level)?,
+    LevelAction::Set { value } => {
+      detect_profile(controller);
+      controller.set_picture_level(level, *value)?
+    },
+    LevelAction::Status => println!("{}", controller.picture_level(level)?),
+  }
+
+  Ok(())
+}
+
+fn handle_picture(
+  _opts: &Options,
+  action: &PictureAction,
+  controller: ProjectorControl
+) -> Result<()> {
+  let setting = matches!(
+    action,
+    PictureAction::Mode { value: Some(_) }
+      | PictureAction::ColorTemp { value: Some(_) }
+      | PictureAction::Gamma { value: Some(_) }
+      | PictureAction::Aspect { value: Some(_) }
+  );
+
+  if setting {
+    detect_profile(&controller);
+  }
+
+  match action {
+    PictureAction::Mode { value: Some(mode) } => controller.set_picture_mode(mode)?,
+    PictureAction::Mode { value: None } => println!("{}", controller.picture_mode()?),
+
+    PictureAction::Brightness(a) => handle_picture_level(PictureLevel::Brightness, a, &controller)?,
+    PictureAction::Contrast(a) => handle_picture_level(PictureLevel::Contrast, a, &controller)?,
+    PictureAction::Color(a) => handle_picture_level(PictureLevel::Color, a, &controller)?,
+    PictureAction::Sharpness(a) => handle_picture_level(PictureLevel::Sharpness, a, &controller)?,
+
+    PictureAction::ColorTemp { value: Some(ct) } => controller.set_color_temperature(ct)?,
+    PictureAction::ColorTemp { value: None } => println!("{}", controller.color_temperature()?),
+
+    PictureAction::Gamma { value: Some(gamma) } => controller.set_gamma(gamma)?,
+    PictureAction::Gamma { value: None } => println!("{}", controller.gamma()?),
+
+    PictureAction::Aspect { value: Some(aspect) } => controller.set_aspect_ratio(aspect)?,
+    PictureAction::Aspect { value: None } => println!("{}", controller.aspect_ratio()?),
+
+    PictureAction::Status => {
+      show("mode", controller.picture_mode());
+      for level in PictureLevel::ALL {
+        show(&level.to_string(), controller.picture_level(level));
+      }
+      show("color temperature", controller.color_temperature());
+      show("gamma", controller.gamma());
+      show("aspect", controller.aspect_ratio());
+    }
+  }
+
+  Ok(())
+}
+
+/// Prints one line of a status report. Not every model supports every
+/// setting, so failures are reported inline.
+fn show<T: fmt::Display>(name: &str, value: benq_control::Result<T>) {
+  match value {
+    Ok(v) => println!("{}: {}", name, v),
+    Err(e) => println!("{}: ({})", name, e),
+  }
+}
+
+fn handle_install(
+  _opts: &Options,
+  action: &InstallAction,
+  controller: ProjectorControl
+) -> Result<()> {
+  match action {
+    InstallAction::Position { value: Some(position) } => {
+      detect_profile(&controller);
+      controller.set_position(position)?
+    },
+    InstallAction::Position { value: None } => println!("{}", controller.position()?),
+
+    InstallAction::Keystone { axis, action } => match action {
+      KeystoneAction::Up => controller.keystone_up(*axis)?,
+      KeystoneAction::Down => controller.keystone_down(*axis)?,
+      KeystoneAction::Set { value } => {
+        detect_profile(&controller);
+        controller.set_keystone(*axis, *value)?
+      },
+      KeystoneAction::Status => println!("{}", controller.keystone(*axis)?),
+    },
+
+    InstallAction::Language { value: Some(language) } => {
+      detect_profile(&controller);
+      controller.set_language(language)?
+    },
+    InstallAction::Language { value: None } => println!("{}", controller.language()?),
+
+    InstallAction::Status => {
+      show("position", controller.position());
+      show("vertical keystone", controller.keystone(KeystoneAxis::Vertical));
+      show("horizontal keystone", controller.keystone(KeystoneAxis::Horizontal));
+      show("language", controller.language());
+    }
+  }
+
+  Ok(())
+}
+
+fn handle_lamp(
+  _opts: &Options,
+  action: &LampAction,
+  controller: ProjectorControl
+) -> Result<()> {
+  match action {
+    LampAction::Mode { value: Some(mode) } => {
+      detect_profile(&controller);
+      controller.set_lamp_mode(mode)?
+    },
+    LampAction::Mode { value: None } => println!("{}", controller.lamp_mode()?),
+    LampAction::Hours => println!("{}", controller.lamp_hours()?),
+    LampAction::Status => {
+      detect_profile(&controller);
+
+      let status = controller.maintenance()?;
+      println!("mode: {}", status.lamp_mode);
+      for (i, hours) in status.lamp_hours.iter().enumerate() {
+        println!("lamp {} hours: {}", i + 1, hours);
+      }
+      if let Some(hours) = status.filter_hours {
+        println!("filter hours: {}", hours);
+      }
+    }
   }
 
   Ok(())
 }
 
-async fn handle_exec(
+fn handle_press(
+  _opts: &Options,
+  action: &PressAction,
+  controller: ProjectorControl
+) -> Result<()> {
+  controller.press_sequence(&action.keys)?;
+
+  Ok(())
+}
+
+fn is_toml(path: &Path) -> bool {
+  matches!(path.extension(), Some(ext) if ext.eq_ignore_ascii_case("toml"))
+}
+
+fn read_snapshot(path: &Path) -> Result<Snapshot> {
+  let contents = fs::read_to_string(path)
+    .with_context(|| format!("reading snapshot {}", path.display()))?;
+
+  let snapshot = if is_toml(path) {
+    toml::from_str(&contents)?
+  } else {
+    serde_json::from_str(&contents)?
+  };
+
+  Ok(snapshot)
+}
+
+fn write_snapshot(path: Option<&Path>, snapshot: &Snapshot) -> Result<()> {
+  match path {
+    Some(path) => {
+      let contents = if is_toml(path) {
+        toml::to_string(snapshot)?
+      } else {
+        serde_json::to_string_pretty(snapshot)?
+      };
+
+      fs::write(path, contents).with_context(|| format!("writing snapshot {}", path.display()))?;
+    },
+    None => println!("{}", serde_json::to_string_pretty(snapshot)?)
+  }
+
+  Ok(())
+}
+
+fn print_diff(left: &Snapshot, right: &Snapshot) {
+  for diff in left.diff(right) {
+    println!("{}", diff);
+  }
+}
+
+fn diff_files(path: &Path, other: &Path) -> Result<()> {
+  print_diff(&read_snapshot(path)?, &read_snapshot(other)?);
+  Ok(())
+}
+
+fn handle_snapshot(
+  _opts: &Options,
+  action: &SnapshotAction,
+  controller: ProjectorControl
+) -> Result<()> {
+  // comparing two files doesn't involve the projector
+  if let SnapshotAction::Diff { path, other: Some(other) } = action {
+    return diff_files(path, other);
+  }
+
+  detect_profile(&controller);
+
+  match action {
+    SnapshotAction::Save { path } => write_snapshot(path.as_deref(), &controller.snapshot()?)?,
+    SnapshotAction::Restore { path } => {
+      let snapshot = read_snapshot(path)?;
+      let model = controller.profile().model;
+      if !snapshot.model.eq_ignore_ascii_case(&model) {
+        warn!("snapshot was taken from a {}, restoring to a {}", snapshot.model, model);
+      }
+
+      let report = controller.restore(&snapshot)?;
+      info!("restored {} settings", report.applied.len());
+      for (key, e) in &report.failed {
+        error!("could not restore {}: {}", key, e);
+      }
+
+      if !report.is_ok() {
+        return Err(eyre!("{} settings could not be restored", report.failed.len()));
+      }
+    },
+    SnapshotAction::Diff { path, .. } => {
+      print_diff(&read_snapshot(path)?, &controller.snapshot()?)
+    },
+  }
+
+  Ok(())
+}
+
+fn handle_run(
+  _opts: &Options,
+  action: &RunAction,
+  controller: ProjectorControl
+) -> Result<()> {
+  let contents = fs::read_to_string(&action.path)
+    .with_context(|| format!("reading sequence {}", action.path.display()))?;
+
+  let mut sequence: Sequence = serde_json::from_str(&contents)
+    .with_context(|| format!("parsing sequence {}", action.path.display()))?;
+  if action.keep_going {
+    sequence.on_error = OnError::Continue;
+  }
+
+  let report = controller.run_sequence(&sequence)?;
+  for step in &report.steps {
+    println!("{}", step);
+  }
+
+  if !report.is_ok() {
+    return Err(eyre!("sequence did not complete successfully"));
+  }
+
+  Ok(())
+}
+
+fn handle_exec(
   _opts: &Options,
   action: &ExecAction,
   controller: ProjectorControl
 ) -> Result<()> {
   info!("exec command: {:?}", action.command);
 
-  let res = controller.submit_command(action.command.clone()).await?;
+  let res = controller.submit_command(action.command.clone())?;
   debug!("exec response: {:?}", res);
 
   if let Some(r) = res {
@@ -232,8 +879,40 @@ async fn handle_exec(
   Ok(())
 }
 
-#[tokio::main]
-async fn main() -> Result<()> {
+fn handle_detect(opts: &Options) -> Result<()> {
+  let path = match &opts.device {
+    PortSelector::Path(path) => path,
+    selector => return Err(eyre!("detect requires a device path, not {}", selector))
+  };
+
+  let detected = detect::detect(path, PORT_TIMEOUT, &opts.protocol_options())?;
+
+  println!("line settings: {}", detected.settings);
+  println!("model: {}", detected.model.as_deref().unwrap_or("unknown"));
+
+  Ok(())
+}
+
+fn handle_ports() -> Result<()> {
+  for port in discovery::list_ports(None)? {
+    match port.port_type {
+      SerialPortType::UsbPort(info) => println!(
+        "{}\tusb:{:04x}:{:04x}{}\t{} {}",
+        port.port_name,
+        info.vid,
+        info.pid,
+        info.serial_number.map(|s| format!(":{}", s)).unwrap_or_default(),
+        info.manufacturer.unwrap_or_default(),
+        info.product.unwrap_or_default(),
+      ),
+      _ => println!("{}", port.port_name),
+    }
+  }
+
+  Ok(())
+}
+
+fn main() -> Result<()> {
   color_eyre::install()?;
 
   let env = env_logger::Env::default()
@@ -247,19 +926,63 @@ async fn main() -> Result<()> {
   let opts: Options = Options::from_args();
   debug!("options: {:?}", opts);
 
-  let port = serialport::new(&opts.device, opts.baud_rate)
-    .timeout(Duration::from_millis(50))
-    .open()?;
+  if let Action::Detect = &opts.action {
+    return handle_detect(&opts);
+  }
 
-  let controller = ProjectorControl::new(port);
+  if let Action::Ports = &opts.action {
+    return handle_ports();
+  }
+
+  // comparing two files doesn't need a projector
+  if let Action::Snapshot(SnapshotAction::Diff { path, other: Some(other) }) = &opts.action {
+    return diff_files(path, other);
+  }
 
-  match &opts.action {
-    Action::Power(action) => handle_power(&opts, action, controller).await?,
-    Action::Source(action) => handle_source(&opts, action, controller).await?,
-    Action::Volume(action) => handle_volume(&opts, action, controller).await?,
-    Action::Mute(action) => handle_mute(&opts, action, controller).await?,
-    Action::Exec(action) => handle_exec(&opts, action, controller).await?,
+  let port = discovery::open(
+    &opts.device,
+    opts.baud_rate,
+    PORT_TIMEOUT,
+    &opts.protocol_options()
+  )?;
+
+  let port: Box<dyn Transport> = match &opts.record {
+    Some(path) => {
+      let transcript = File::create(path)
+        .with_context(|| format!("creating transcript {}", path.display()))?;
+
+      Box::new(Recorder::new(port, transcript))
+    },
+    None => Box::new(port)
   };
 
-  Ok(())
+  let controller = ProjectorControl::builder()
+    .protocol(opts.protocol_options())
+    .build_blocking(port);
+
+  let result = match &opts.action {
+    Action::Power(action) => handle_power(&opts, action, controller.clone()),
+    Action::Source(action) => handle_source(&opts, action, controller.clone()),
+    Action::Volume(action) => handle_volume(&opts, action, controller.clone()),
+    Action::Mute(action) => handle_mute(&opts, action, controller.clone()),
+    Action::Blank(action) => handle_blank(&opts, action, controller.clone()),
+    Action::Freeze(action) => handle_freeze(&opts, action, controller.clone()),
+    Action::ThreeD(action) => handle_three_d(&opts, action, controller.clone()),
+    Action::Picture(action) => handle_picture(&opts, action, controller.clone()),
+    Action::Lamp(action) => handle_lamp(&opts, action, controller.clone()),
+    Action::Install(action) => handle_install(&opts, action, controller.clone()),
+    Action::Snapshot(action) => handle_snapshot(&opts, action, controller.clone()),
+    Action::Run(action) => handle_run(&opts, action, controller.clone()),
+    Action::Press(action) => handle_press(&opts, action, controller.clone()),
+    Action::Exec(action) => handle_exec(&opts, action, controller.clone()),
+    Action::Detect | Action::Ports => unreachable!(),
+  };
+
+  if result.is_err() && opts.journal {
+    for entry in controller.journal() {
+      eprintln!("{}", entry);
+    }
+  }
+
+  result
 }
@@ -0,0 +1,437 @@
+//! A synchronous wrapper around [`ProjectorControl`](crate::ProjectorControl)
+//! for programs that don't otherwise use async.
+//!
+//! Commands are still executed on the usual background thread; each method
+//! simply blocks the calling thread until the response arrives. No async
+//! runtime is needed.
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use benq_control::PowerState;
+//! use benq_control::blocking::ProjectorControl;
+//! use benq_control::detect::LineSettings;
+//!
+//! let port = LineSettings::new(115_200).open("/dev/ttyUSB0", Duration::from_millis(50))?;
+//! let mut projector = ProjectorControl::new(port);
+//! projector.set_timeout(Some(Duration::from_secs(5)));
+//!
+//! if projector.power()? == PowerState::Off {
+//!   projector.set_power(PowerState::On)?;
+//! }
+//! # Ok::<(), benq_control::Error>(())
+//! ```
+
+use std::future::Future;
+use std::pin::pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread::{self, Thread};
+use std::time::{Duration, Instant};
+
+use futures::FutureExt;
+
+use crate::{
+  AspectRatio, ColorTemperature, Command, CommandResult, ConnectionState, Error, Gamma, JournalEntry,
+  KeystoneAxis, LampMode, Language, MaintenanceStatus, ModelProfile, PictureLevel, PictureMode,
+  PowerProgress, PowerState, PowerWait, ProjectorControlBuilder, ProjectorPosition, QueueStatus,
+  RemoteKey, RestoreReport, Result, Sequence, SequenceReport, Snapshot, Source, ThreeDMode
+};
+use crate::protocol::Transport;
+
+/// Wakes a thread parked in [`block_on`].
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+  fn wake(self: Arc<Self>) {
+    self.0.unpark();
+  }
+}
+
+/// Runs `future` to completion on the current thread, giving up with
+/// [`Error::Timeout`] after `timeout`.
+///
+/// The futures returned by `ProjectorControl` only wait on a queue or channel, so no
+/// runtime is needed to drive them.
+fn block_on<T, F>(future: F, timeout: Option<Duration>) -> Result<T>
+where
+  F: Future<Output = Result<T>>
+{
+  let deadline = timeout.map(|t| Instant::now() + t);
+  let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+  let mut cx = Context::from_waker(&waker);
+  let mut future = pin!(future);
+
+  loop {
+    if let Poll::Ready(result) = future.as_mut().poll(&mut cx) {
+      return result;
+    }
+
+    match deadline {
+      Some(deadline) => {
+        let now = Instant::now();
+        if now >= deadline {
+          return Err(Error::Timeout);
+        }
+
+        thread::park_timeout(deadline - now);
+      },
+      None => thread::park()
+    }
+  }
+}
+
+/// A synchronous projector controller. See the [module docs](self).
+///
+/// Like the async version, this is a cheaply cloneable handle.
+#[derive(Clone)]
+pub struct ProjectorControl {
+  inner: crate::ProjectorControl,
+  timeout: Option<Duration>,
+}
+
+impl From<crate::ProjectorControl> for ProjectorControl {
+  fn from(inner: crate::ProjectorControl) -> Self {
+    ProjectorControl { inner, timeout: None }
+  }
+}
+
+impl ProjectorControl {
+  /// Starts processing commands on the given port with default options.
+  pub fn new<T: Transport + 'static>(port: T) -> ProjectorControl {
+    crate::ProjectorControl::new(port).into()
+  }
+
+  /// Returns a builder; finish it with
+  /// [`build_blocking`](crate::ProjectorControlBuilder::build_blocking).
+  pub fn builder() -> ProjectorControlBuilder {
+    ProjectorControlBuilder::default()
+  }
+
+  /// Sets how long each method may wait for its response, or `None` (the
+  /// default) to wait indefinitely.
+  ///
+  /// The timeout includes time spent waiting behind earlier commands, such as
+  /// the pause after powering on. A command that times out is not removed
+  /// from the queue and may still be executed later.
+  pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+    self.timeout = timeout;
+  }
+
+  pub fn timeout(&self) -> Option<Duration> {
+    self.timeout
+  }
+
+  /// Returns the underlying async controller.
+  pub fn as_async(&self) -> &crate::ProjectorControl {
+    &self.inner
+  }
+
+  fn wait<T>(&self, future: impl Future<Output = Result<T>>) -> Result<T> {
+    block_on(future, self.timeout)
+  }
+
+  /// Returns the current state of the serial connection.
+  pub fn connection_state(&self) -> ConnectionState {
+    self.inner.connection_state()
+  }
+
+  /// Executes a command and waits for its response.
+  pub fn submit_command(&self, command: impl Into<Command>) -> CommandResult {
+    self.wait(self.inner.submit_command(command))
+  }
+
+  /// Executes a background command and waits for its response. See
+  /// [`ProjectorControl::submit_background`](crate::ProjectorControl::submit_background).
+  pub fn submit_background(&self, command: impl Into<Command>) -> CommandResult {
+    self.wait(self.inner.submit_background(command))
+  }
+
+  /// Returns the queue depth and the command currently executing.
+  pub fn queue_status(&self) -> QueueStatus {
+    self.inner.queue_status()
+  }
+
+  /// Returns recently executed commands with the raw bytes exchanged, oldest
+  /// first.
+  pub fn journal(&self) -> Vec<JournalEntry> {
+    self.inner.journal()
+  }
+
+  /// Stops the processing thread once all queued commands have run.
+  pub fn stop(self) -> CommandResult {
+    block_on(self.inner.stop(), self.timeout)
+  }
+
+  /// Stops processing once all queued commands have run, and waits for the
+  /// processing thread to exit and close the port.
+  pub fn shutdown(self) -> Result<()> {
+    block_on(self.inner.shutdown().map(Ok), self.timeout)
+  }
+
+  /// See [`ProjectorControl::power`](crate::ProjectorControl::power).
+  pub fn power(&self) -> Result<PowerState> {
+    self.wait(self.inner.power())
+  }
+
+  /// See [`ProjectorControl::set_power`](crate::ProjectorControl::set_power).
+  pub fn set_power(&self, state: PowerState) -> Result<()> {
+    self.wait(self.inner.set_power(state))
+  }
+
+  /// See [`ProjectorControl::power_on_and_wait`](crate::ProjectorControl::power_on_and_wait).
+  pub fn power_on_and_wait(
+    &self,
+    wait: PowerWait,
+    progress: impl FnMut(PowerProgress)
+  ) -> Result<()> {
+    self.wait(self.inner.power_on_and_wait(wait, progress))
+  }
+
+  /// See [`ProjectorControl::power_off_and_wait`](crate::ProjectorControl::power_off_and_wait).
+  pub fn power_off_and_wait(
+    &self,
+    wait: PowerWait,
+    progress: impl FnMut(PowerProgress)
+  ) -> Result<()> {
+    self.wait(self.inner.power_off_and_wait(wait, progress))
+  }
+
+  /// See [`ProjectorControl::source`](crate::ProjectorControl::source).
+  pub fn source(&self) -> Result<Source> {
+    self.wait(self.inner.source())
+  }
+
+  /// See [`ProjectorControl::set_source`](crate::ProjectorControl::set_source).
+  pub fn set_source(&self, source: &Source) -> Result<()> {
+    self.wait(self.inner.set_source(source))
+  }
+
+  /// See [`ProjectorControl::volume`](crate::ProjectorControl::volume).
+  pub fn volume(&self) -> Result<u8> {
+    self.wait(self.inner.volume())
+  }
+
+  /// See [`ProjectorControl::set_volume`](crate::ProjectorControl::set_volume).
+  pub fn set_volume(&self, volume: u8) -> Result<()> {
+    self.wait(self.inner.set_volume(volume))
+  }
+
+  /// See [`ProjectorControl::volume_up`](crate::ProjectorControl::volume_up).
+  pub fn volume_up(&self) -> Result<()> {
+    self.wait(self.inner.volume_up())
+  }
+
+  /// See [`ProjectorControl::volume_down`](crate::ProjectorControl::volume_down).
+  pub fn volume_down(&self) -> Result<()> {
+    self.wait(self.inner.volume_down())
+  }
+
+  /// See [`ProjectorControl::muted`](crate::ProjectorControl::muted).
+  pub fn muted(&self) -> Result<bool> {
+    self.wait(self.inner.muted())
+  }
+
+  /// See [`ProjectorControl::set_muted`](crate::ProjectorControl::set_muted).
+  pub fn set_muted(&self, muted: bool) -> Result<()> {
+    self.wait(self.inner.set_muted(muted))
+  }
+
+  /// See [`ProjectorControl::profile`](crate::ProjectorControl::profile).
+  pub fn profile(&self) -> ModelProfile {
+    self.inner.profile()
+  }
+
+  /// See [`ProjectorControl::set_profile`](crate::ProjectorControl::set_profile).
+  pub fn set_profile(&self, profile: ModelProfile) {
+    self.inner.set_profile(profile)
+  }
+
+  /// See [`ProjectorControl::detect_profile`](crate::ProjectorControl::detect_profile).
+  pub fn detect_profile(&self) -> Result<ModelProfile> {
+    self.wait(self.inner.detect_profile())
+  }
+
+  /// See [`ProjectorControl::model`](crate::ProjectorControl::model).
+  pub fn model(&self) -> Result<String> {
+    self.wait(self.inner.model())
+  }
+
+  /// See [`ProjectorControl::picture_mode`](crate::ProjectorControl::picture_mode).
+  pub fn picture_mode(&self) -> Result<PictureMode> {
+    self.wait(self.inner.picture_mode())
+  }
+
+  /// See [`ProjectorControl::set_picture_mode`](crate::ProjectorControl::set_picture_mode).
+  pub fn set_picture_mode(&self, mode: &PictureMode) -> Result<()> {
+    self.wait(self.inner.set_picture_mode(mode))
+  }
+
+  /// See [`ProjectorControl::picture_level`](crate::ProjectorControl::picture_level).
+  pub fn picture_level(&self, level: PictureLevel) -> Result<u8> {
+    self.wait(self.inner.picture_level(level))
+  }
+
+  /// See [`ProjectorControl::set_picture_level`](crate::ProjectorControl::set_picture_level).
+  pub fn set_picture_level(&self, level: PictureLevel, value: u8) -> Result<()> {
+    self.wait(self.inner.set_picture_level(level, value))
+  }
+
+  /// See [`ProjectorControl::picture_level_up`](crate::ProjectorControl::picture_level_up).
+  pub fn picture_level_up(&self, level: PictureLevel) -> Result<()> {
+    self.wait(self.inner.picture_level_up(level))
+  }
+
+  /// See [`ProjectorControl::picture_level_down`](crate::ProjectorControl::picture_level_down).
+  pub fn picture_level_down(&self, level: PictureLevel) -> Result<()> {
+    self.wait(self.inner.picture_level_down(level))
+  }
+
+  /// See [`ProjectorControl::color_temperature`](crate::ProjectorControl::color_temperature).
+  pub fn color_temperature(&self) -> Result<ColorTemperature> {
+    self.wait(self.inner.color_temperature())
+  }
+
+  /// See [`ProjectorControl::set_color_temperature`](crate::ProjectorControl::set_color_temperature).
+  pub fn set_color_temperature(&self, temperature: &ColorTemperature) -> Result<()> {
+    self.wait(self.inner.set_color_temperature(temperature))
+  }
+
+  /// See [`ProjectorControl::gamma`](crate::ProjectorControl::gamma).
+  pub fn gamma(&self) -> Result<Gamma> {
+    self.wait(self.inner.gamma())
+  }
+
+  /// See [`ProjectorControl::set_gamma`](crate::ProjectorControl::set_gamma).
+  pub fn set_gamma(&self, gamma: &Gamma) -> Result<()> {
+    self.wait(self.inner.set_gamma(gamma))
+  }
+
+  /// See [`ProjectorControl::aspect_ratio`](crate::ProjectorControl::aspect_ratio).
+  pub fn aspect_ratio(&self) -> Result<AspectRatio> {
+    self.wait(self.inner.aspect_ratio())
+  }
+
+  /// See [`ProjectorControl::set_aspect_ratio`](crate::ProjectorControl::set_aspect_ratio).
+  pub fn set_aspect_ratio(&self, aspect: &AspectRatio) -> Result<()> {
+    self.wait(self.inner.set_aspect_ratio(aspect))
+  }
+
+  /// See [`ProjectorControl::lamp_hours`](crate::ProjectorControl::lamp_hours).
+  pub fn lamp_hours(&self) -> Result<u32> {
+    self.wait(self.inner.lamp_hours())
+  }
+
+  /// See [`ProjectorControl::lamp_mode`](crate::ProjectorControl::lamp_mode).
+  pub fn lamp_mode(&self) -> Result<LampMode> {
+    self.wait(self.inner.lamp_mode())
+  }
+
+  /// See [`ProjectorControl::set_lamp_mode`](crate::ProjectorControl::set_lamp_mode).
+  pub fn set_lamp_mode(&self, mode: &LampMode) -> Result<()> {
+    self.wait(self.inner.set_lamp_mode(mode))
+  }
+
+  /// See [`ProjectorControl::blanked`](crate::ProjectorControl::blanked).
+  pub fn blanked(&self) -> Result<bool> {
+    self.wait(self.inner.blanked())
+  }
+
+  /// See [`ProjectorControl::set_blank`](crate::ProjectorControl::set_blank).
+  pub fn set_blank(&self, blank: bool) -> Result<()> {
+    self.wait(self.inner.set_blank(blank))
+  }
+
+  /// See [`ProjectorControl::frozen`](crate::ProjectorControl::frozen).
+  pub fn frozen(&self) -> Result<bool> {
+    self.wait(self.inner.frozen())
+  }
+
+  /// See [`ProjectorControl::set_freeze`](crate::ProjectorControl::set_freeze).
+  pub fn set_freeze(&self, freeze: bool) -> Result<()> {
+    self.wait(self.inner.set_freeze(freeze))
+  }
+
+  /// See [`ProjectorControl::three_d_mode`](crate::ProjectorControl::three_d_mode).
+  pub fn three_d_mode(&self) -> Result<ThreeDMode> {
+    self.wait(self.inner.three_d_mode())
+  }
+
+  /// See [`ProjectorControl::set_three_d_mode`](crate::ProjectorControl::set_three_d_mode).
+  pub fn set_three_d_mode(&self, mode: &ThreeDMode) -> Result<()> {
+    self.wait(self.inner.set_three_d_mode(mode))
+  }
+
+  /// See [`ProjectorControl::position`](crate::ProjectorControl::position).
+  pub fn position(&self) -> Result<ProjectorPosition> {
+    self.wait(self.inner.position())
+  }
+
+  /// See [`ProjectorControl::set_position`](crate::ProjectorControl::set_position).
+  pub fn set_position(&self, position: &ProjectorPosition) -> Result<()> {
+    self.wait(self.inner.set_position(position))
+  }
+
+  /// See [`ProjectorControl::keystone`](crate::ProjectorControl::keystone).
+  pub fn keystone(&self, axis: KeystoneAxis) -> Result<i8> {
+    self.wait(self.inner.keystone(axis))
+  }
+
+  /// See [`ProjectorControl::set_keystone`](crate::ProjectorControl::set_keystone).
+  pub fn set_keystone(&self, axis: KeystoneAxis, value: i8) -> Result<()> {
+    self.wait(self.inner.set_keystone(axis, value))
+  }
+
+  /// See [`ProjectorControl::keystone_up`](crate::ProjectorControl::keystone_up).
+  pub fn keystone_up(&self, axis: KeystoneAxis) -> Result<()> {
+    self.wait(self.inner.keystone_up(axis))
+  }
+
+  /// See [`ProjectorControl::keystone_down`](crate::ProjectorControl::keystone_down).
+  pub fn keystone_down(&self, axis: KeystoneAxis) -> Result<()> {
+    self.wait(self.inner.keystone_down(axis))
+  }
+
+  /// See [`ProjectorControl::language`](crate::ProjectorControl::language).
+  pub fn language(&self) -> Result<Language> {
+    self.wait(self.inner.language())
+  }
+
+  /// See [`ProjectorControl::set_language`](crate::ProjectorControl::set_language).
+  pub fn set_language(&self, language: &Language) -> Result<()> {
+    self.wait(self.inner.set_language(language))
+  }
+
+  /// See [`ProjectorControl::press`](crate::ProjectorControl::press).
+  pub fn press(&self, key: &RemoteKey) -> Result<()> {
+    self.wait(self.inner.press(key))
+  }
+
+  /// See [`ProjectorControl::press_sequence`](crate::ProjectorControl::press_sequence).
+  pub fn press_sequence(&self, keys: &[RemoteKey]) -> Result<()> {
+    self.wait(self.inner.press_sequence(keys))
+  }
+
+  /// See [`ProjectorControl::maintenance`](crate::ProjectorControl::maintenance).
+  pub fn maintenance(&self) -> Result<MaintenanceStatus> {
+    self.wait(self.inner.maintenance())
+  }
+
+  /// See [`ProjectorControl::run_sequence`](crate::ProjectorControl::run_sequence).
+  /// Fails only if the [timeout](Self::set_timeout) elapses first, which
+  /// applies to the sequence as a whole.
+  pub fn run_sequence(&self, sequence: &Sequence) -> Result<SequenceReport> {
+    self.wait(self.inner.run_sequence(sequence).map(Ok))
+  }
+
+  /// See [`ProjectorControl::snapshot`](crate::ProjectorControl::snapshot).
+  pub fn snapshot(&self) -> Result<Snapshot> {
+    self.wait(self.inner.snapshot())
+  }
+
+  /// See [`ProjectorControl::restore`](crate::ProjectorControl::restore).
+  /// Fails only if the [timeout](Self::set_timeout) elapses first, which
+  /// applies to the restore as a whole.
+  pub fn restore(&self, snapshot: &Snapshot) -> Result<RestoreReport> {
+    self.wait(self.inner.restore(snapshot).map(Ok))
+  }
+}
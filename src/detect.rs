@@ -0,0 +1,212 @@
+//! Detection of a projector's serial line settings.
+//!
+//! BenQ projectors default to various baud rates depending on their age and
+//! the rate can be changed in the service menu, so rather than guessing we can
+//! try each combination in turn and ask the projector for its model name.
+
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+use log::{debug, info};
+use serialport::{DataBits, Parity, SerialPort, StopBits};
+
+use crate::{Error, Result};
+use crate::protocol::{send_get, ProtocolOptions, Transport};
+
+/// Baud rates supported by BenQ projectors, in the order they are tried.
+pub const BAUD_RATES: &[u32] = &[115_200, 57_600, 38_400, 19_200, 9_600, 4_800, 2_400];
+
+/// Parity and stop bit combinations, in the order they are tried.
+const LINE_FORMATS: &[(Parity, StopBits)] = &[
+  (Parity::None, StopBits::One),
+  (Parity::Even, StopBits::One),
+  (Parity::Odd, StopBits::One),
+  (Parity::None, StopBits::Two),
+];
+
+/// Serial line settings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineSettings {
+  pub baud_rate: u32,
+  pub data_bits: DataBits,
+  pub parity: Parity,
+  pub stop_bits: StopBits,
+}
+
+impl LineSettings {
+  /// Returns 8N1 settings at the given baud rate.
+  pub fn new(baud_rate: u32) -> LineSettings {
+    LineSettings {
+      baud_rate,
+      data_bits: DataBits::Eight,
+      parity: Parity::None,
+      stop_bits: StopBits::One,
+    }
+  }
+
+  /// Opens the serial port at `path` with these settings.
+  pub fn open(&self, path: &str, timeout: Duration) -> Result<Box<dyn SerialPort>> {
+    Ok(
+      serialport::new(path, self.baud_rate)
+        .data_bits(self.data_bits)
+        .parity(self.parity)
+        .stop_bits(self.stop_bits)
+        .timeout(timeout)
+        .open()?
+    )
+  }
+
+  /// Applies these settings to an open port.
+  pub fn apply(&self, port: &mut dyn SerialPort) -> Result<()> {
+    port.set_baud_rate(self.baud_rate)?;
+    port.set_data_bits(self.data_bits)?;
+    port.set_parity(self.parity)?;
+    port.set_stop_bits(self.stop_bits)?;
+
+    Ok(())
+  }
+}
+
+impl fmt::Display for LineSettings {
+  /// Formats settings in the usual shorthand, e.g. `115200 8N1`
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let data_bits = match self.data_bits {
+      DataBits::Five => 5,
+      DataBits::Six => 6,
+      DataBits::Seven => 7,
+      DataBits::Eight => 8,
+    };
+
+    let parity = match self.parity {
+      Parity::None => 'N',
+      Parity::Odd => 'O',
+      Parity::Even => 'E',
+    };
+
+    let stop_bits = match self.stop_bits {
+      StopBits::One => 1,
+      StopBits::Two => 2,
+    };
+
+    write!(f, "{} {}{}{}", self.baud_rate, data_bits, parity, stop_bits)
+  }
+}
+
+/// A baud rate option, either fixed or automatically detected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BaudRate {
+  Auto,
+  Fixed(u32),
+}
+
+impl FromStr for BaudRate {
+  type Err = Error;
+
+  fn from_str(s: &str) -> Result<Self> {
+    if s.eq_ignore_ascii_case("auto") {
+      return Ok(BaudRate::Auto);
+    }
+
+    s.parse::<u32>()
+      .map(BaudRate::Fixed)
+      .map_err(|_| Error::InvalidBaudRate(s.to_string()))
+  }
+}
+
+impl fmt::Display for BaudRate {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      BaudRate::Auto => write!(f, "auto"),
+      BaudRate::Fixed(rate) => write!(f, "{}", rate),
+    }
+  }
+}
+
+/// A serial port configured with detected line settings.
+pub struct DetectedPort<T = Box<dyn SerialPort>> {
+  pub port: T,
+  pub settings: LineSettings,
+
+  /// The projector's model name, if it was willing to share it
+  pub model: Option<String>,
+}
+
+/// Checks whether the projector answers a model name query with the port's
+/// current settings.
+///
+/// Returns `Some` if the projector answered sensibly (the inner value being its
+/// model, if it didn't refuse with "Block item").
+fn confirm<T: Transport>(port: &mut T, options: &ProtocolOptions) -> Option<Option<String>> {
+  match send_get(port, "modelname", options) {
+    Ok(Some(r)) => {
+      let (key, model) = r.split_once('=')?;
+      if key.eq_ignore_ascii_case("modelname") {
+        Some(Some(model.to_string()))
+      } else {
+        None
+      }
+    },
+
+    // an error response is still a response
+    Err(Error::ResponseBlockItem) => Some(None),
+
+    Ok(None) | Err(_) => None
+  }
+}
+
+/// Tries each standard baud rate and line format on the port at `path` until
+/// the projector responds to a harmless `modelname=?` query.
+///
+/// The port is reopened for each attempt. This can take several seconds if the
+/// projector's settings are near the end of the list (or the projector is not
+/// connected at all).
+pub fn detect(path: &str, timeout: Duration, options: &ProtocolOptions) -> Result<DetectedPort> {
+  detect_with(path, options, |settings| settings.open(path, timeout))
+}
+
+/// Like [`detect`], but calls `open` to get a port with each candidate's
+/// settings in turn, e.g. to detect settings through something other than a
+/// local serial port. `path` is only used in messages.
+///
+/// Errors from `open` abort detection.
+pub fn detect_with<T, F>(path: &str, options: &ProtocolOptions, mut open: F) -> Result<DetectedPort<T>>
+where
+  T: Transport,
+  F: FnMut(LineSettings) -> Result<T>
+{
+  for &(parity, stop_bits) in LINE_FORMATS {
+    for &baud_rate in BAUD_RATES {
+      let settings = LineSettings {
+        baud_rate,
+        parity,
+        stop_bits,
+        ..LineSettings::new(baud_rate)
+      };
+
+      debug!("detect: trying {}", settings);
+      let mut port = open(settings)?;
+
+      if let Some(model) = confirm(&mut port, options) {
+        info!("detected projector ({:?}) on {} at {}", model, path, settings);
+        return Ok(DetectedPort { port, settings, model });
+      }
+    }
+  }
+
+  Err(Error::DetectionFailed { path: path.to_string() })
+}
+
+/// Opens the port at `path`, detecting line settings if `baud_rate` is
+/// [`BaudRate::Auto`].
+pub fn open(
+  path: &str,
+  baud_rate: BaudRate,
+  timeout: Duration,
+  options: &ProtocolOptions
+) -> Result<Box<dyn SerialPort>> {
+  match baud_rate {
+    BaudRate::Auto => Ok(detect(path, timeout, options)?.port),
+    BaudRate::Fixed(rate) => LineSettings::new(rate).open(path, timeout),
+  }
+}
@@ -0,0 +1,200 @@
+//! Discovery of serial ports by USB adapter identity.
+//!
+//! USB serial adapters tend to move between e.g. `/dev/ttyUSB0` and
+//! `/dev/ttyUSB1` across reboots, so it's often more reliable to select a port
+//! by its adapter's vendor and product IDs (and serial number, if there are
+//! several identical adapters) than by its path.
+//!
+//! Note that on Linux, USB identity is only available when built with the
+//! `libudev` feature; otherwise every port is reported as being of unknown
+//! type and only [`PortSelector::Path`] and [`PortSelector::Auto`] are useful.
+
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+use log::{debug, info};
+use serialport::{SerialPort, SerialPortInfo, SerialPortType, UsbPortInfo};
+
+use crate::{Error, Result};
+use crate::detect::{self, BaudRate};
+use crate::protocol::{wait_for_prompt, ProtocolOptions};
+
+/// Matches USB serial adapters by identity. Unset fields match anything.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UsbFilter {
+  pub vid: Option<u16>,
+  pub pid: Option<u16>,
+  pub serial_number: Option<String>,
+}
+
+impl UsbFilter {
+  pub fn matches(&self, info: &UsbPortInfo) -> bool {
+    self.vid.map_or(true, |vid| vid == info.vid)
+      && self.pid.map_or(true, |pid| pid == info.pid)
+      && self.serial_number.as_ref().map_or(true, |s| {
+        info.serial_number.as_ref() == Some(s)
+      })
+  }
+}
+
+impl fmt::Display for UsbFilter {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "usb")?;
+
+    let fields = [
+      self.vid.map(|v| format!("{:04x}", v)),
+      self.pid.map(|p| format!("{:04x}", p)),
+      self.serial_number.clone(),
+    ];
+
+    // trailing unset fields can be omitted, others must be left empty
+    let len = fields.iter().rposition(Option::is_some).map_or(0, |i| i + 1);
+    for field in &fields[..len] {
+      write!(f, ":{}", field.as_deref().unwrap_or(""))?;
+    }
+
+    Ok(())
+  }
+}
+
+/// Selects a serial port by path, by USB adapter identity, or automatically.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PortSelector {
+  /// A fixed device path, e.g. `/dev/ttyUSB0`
+  Path(String),
+
+  /// The USB adapter matching a filter, written as
+  /// `usb:<vid>[:<pid>[:<serial>]]` with hexadecimal IDs, e.g. `usb:0403:6001`,
+  /// or just `usb` to match any adapter
+  Usb(UsbFilter),
+
+  /// The first port with a projector attached, written as `auto`
+  Auto,
+}
+
+fn parse_usb_id(s: &str, selector: &str) -> Result<Option<u16>> {
+  if s.is_empty() {
+    return Ok(None);
+  }
+
+  u16::from_str_radix(s, 16)
+    .map(Some)
+    .map_err(|_| Error::InvalidPortSelector(selector.to_string()))
+}
+
+impl FromStr for PortSelector {
+  type Err = Error;
+
+  fn from_str(s: &str) -> Result<Self> {
+    if s.eq_ignore_ascii_case("auto") {
+      return Ok(PortSelector::Auto);
+    }
+
+    let spec = match s.strip_prefix("usb") {
+      Some("") => "",
+      Some(spec) if spec.starts_with(':') => &spec[1..],
+      _ => return Ok(PortSelector::Path(s.to_string()))
+    };
+
+    let mut parts = spec.splitn(3, ':');
+    let vid = parse_usb_id(parts.next().unwrap_or(""), s)?;
+    let pid = parse_usb_id(parts.next().unwrap_or(""), s)?;
+    let serial_number = parts.next()
+      .filter(|serial| !serial.is_empty())
+      .map(String::from);
+
+    Ok(PortSelector::Usb(UsbFilter { vid, pid, serial_number }))
+  }
+}
+
+impl fmt::Display for PortSelector {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      PortSelector::Path(path) => write!(f, "{}", path),
+      PortSelector::Usb(filter) => write!(f, "{}", filter),
+      PortSelector::Auto => write!(f, "auto"),
+    }
+  }
+}
+
+/// Lists available serial ports, restricted to USB adapters matching `filter`
+/// if one is given.
+pub fn list_ports(filter: Option<&UsbFilter>) -> Result<Vec<SerialPortInfo>> {
+  let ports = serialport::available_ports()?;
+
+  let filter = match filter {
+    Some(filter) => filter,
+    None => return Ok(ports)
+  };
+
+  Ok(ports.into_iter().filter(|p| match &p.port_type {
+    SerialPortType::UsbPort(info) => filter.matches(info),
+    _ => false
+  }).collect())
+}
+
+/// Checks for a projector on the port at `path`, returning the opened port if
+/// one answered.
+///
+/// With a fixed baud rate this only waits for the projector's prompt; with
+/// [`BaudRate::Auto`] it runs full detection.
+pub fn probe(
+  path: &str,
+  baud_rate: BaudRate,
+  timeout: Duration,
+  options: &ProtocolOptions
+) -> Option<Box<dyn SerialPort>> {
+  let result = match baud_rate {
+    BaudRate::Auto => detect::detect(path, timeout, options).map(|d| Some(d.port)),
+    BaudRate::Fixed(rate) => detect::LineSettings::new(rate)
+      .open(path, timeout)
+      .and_then(|mut port| {
+        Ok(if wait_for_prompt(&mut port, options)? { Some(port) } else { None })
+      })
+  };
+
+  match result {
+    Ok(port) => port,
+    Err(e) => {
+      debug!("probe: no projector on {}: {}", path, e);
+      None
+    }
+  }
+}
+
+/// Opens the port described by `selector`.
+///
+/// If a USB filter matches several adapters, or the selector is
+/// [`PortSelector::Auto`], each candidate is probed and the first with a
+/// projector attached is used. Auto selection tries USB adapters first.
+pub fn open(
+  selector: &PortSelector,
+  baud_rate: BaudRate,
+  timeout: Duration,
+  options: &ProtocolOptions
+) -> Result<Box<dyn SerialPort>> {
+  let mut candidates = match selector {
+    PortSelector::Path(path) => return detect::open(path, baud_rate, timeout, options),
+    PortSelector::Usb(filter) => list_ports(Some(filter))?,
+    PortSelector::Auto => list_ports(None)?,
+  };
+
+  if let [port] = candidates.as_slice() {
+    if let PortSelector::Usb(_) = selector {
+      info!("selected port {} for {}", port.port_name, selector);
+      return detect::open(&port.port_name, baud_rate, timeout, options);
+    }
+  }
+
+  candidates.sort_by_key(|p| !matches!(p.port_type, SerialPortType::UsbPort(_)));
+  for candidate in &candidates {
+    debug!("probing {} for {}", candidate.port_name, selector);
+    if let Some(port) = probe(&candidate.port_name, baud_rate, timeout, options) {
+      info!("selected port {} for {}", candidate.port_name, selector);
+      return Ok(port);
+    }
+  }
+
+  Err(Error::NoMatchingPort { selector: selector.to_string() })
+}
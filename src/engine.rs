@@ -0,0 +1,428 @@
+//! The command engines that own the serial port and execute queued commands.
+//!
+//! Two implementations share the same queue and pacing rules: a blocking one
+//! that runs on a dedicated thread, and an async one that runs as a future on
+//! whatever executor the caller prefers.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use futures::FutureExt;
+use futures::channel::oneshot;
+use futures::future::BoxFuture;
+use log::{trace, debug, info, warn};
+
+use crate::{Command, CommandResult, ConnectionState, Error, Pacing, ReconnectBackoff, Result};
+use crate::journal::{Journal, Tap, Wire};
+use crate::queue::Queue;
+use crate::rt;
+use crate::protocol::{
+  send_action, send_action_async, send_get, send_get_async, send_set, send_set_async,
+  AsyncTransport, ProtocolOptions, Transport
+};
+
+/// Reopens a port after it has been lost.
+pub type PortOpener = Box<dyn FnMut() -> Result<Box<dyn Transport>> + Send>;
+
+/// Asynchronously reopens a port after it has been lost.
+pub type AsyncPortOpener = Box<
+  dyn FnMut() -> BoxFuture<'static, Result<Box<dyn AsyncTransport>>> + Send
+>;
+
+#[derive(Debug)]
+pub(crate) struct SubmittedCommand {
+  pub(crate) command: Command,
+  pub(crate) tx: oneshot::Sender<CommandResult>,
+
+  /// Whether the command may be evicted under
+  /// [`QueuePolicy::DropOldestBackground`](crate::QueuePolicy::DropOldestBackground)
+  pub(crate) background: bool,
+}
+
+fn respond(cmd: SubmittedCommand, result: CommandResult) -> Command {
+  debug!("command {:?} result: {:?}", &cmd.command, &result);
+
+  if let Err(e) = cmd.tx.send(result) {
+    // we can't do much if this fails, but dropping it normally after this
+    // iteration will at least raise Cancelled on the other end (though the
+    // other end probably no longer exists)
+    debug!("command ({:?}) response send failed: {:?}", &cmd.command, e);
+  }
+
+  cmd.command
+}
+
+/// State shared by both engines.
+pub(crate) struct WorkerConfig {
+  pub(crate) backoff: ReconnectBackoff,
+  pub(crate) pacing: Pacing,
+  pub(crate) options: ProtocolOptions,
+  pub(crate) state: Arc<Mutex<ConnectionState>>,
+
+  pub(crate) queue: Arc<Queue>,
+  pub(crate) journal: Arc<Journal>,
+
+  /// Dropped along with the worker to signal that it has exited
+  pub(crate) _done: oneshot::Sender<()>,
+}
+
+impl WorkerConfig {
+  fn set_state(&self, state: ConnectionState) {
+    *self.state.lock().unwrap() = state;
+  }
+
+  /// Returns `true` if reconnection should stop after `attempt` failures.
+  fn out_of_attempts(&self, attempt: u32) -> bool {
+    match self.backoff.max_attempts {
+      Some(max) if attempt >= max => {
+        warn!("giving up reconnecting after {} attempts", attempt);
+        true
+      },
+      _ => false
+    }
+  }
+
+  /// Returns `true` if `cmd` should be skipped because every handle is gone
+  /// and nobody is waiting for its result.
+  fn abandoned(&self, cmd: &SubmittedCommand) -> bool {
+    if self.queue.is_released() && cmd.tx.is_canceled() {
+      debug!("dropping abandoned command: {:?}", &cmd.command);
+      true
+    } else {
+      false
+    }
+  }
+}
+
+impl Drop for WorkerConfig {
+  fn drop(&mut self) {
+    self.queue.close();
+  }
+}
+
+/// Returns how long to wait until `ready_at`, if at all.
+fn remaining(ready_at: Instant) -> Option<Duration> {
+  ready_at.checked_duration_since(Instant::now()).filter(|d| !d.is_zero())
+}
+
+/// Owns the serial port on the processing thread.
+pub(crate) struct Worker {
+  pub(crate) port: Option<Box<dyn Transport>>,
+  pub(crate) reopen: Option<PortOpener>,
+  pub(crate) config: WorkerConfig,
+}
+
+impl Worker {
+  /// Reopens the port, retrying with backoff until it succeeds.
+  ///
+  /// Returns `false` if reconnection is not enabled, every handle has been
+  /// dropped or the backoff's attempts have run out.
+  fn reconnect(&mut self) -> bool {
+    let reopen = match self.reopen.as_mut() {
+      Some(reopen) => reopen,
+      None => {
+        self.config.set_state(ConnectionState::Disconnected);
+        return false;
+      }
+    };
+
+    let mut delay = self.config.backoff.initial;
+    let mut attempt = 0;
+    loop {
+      self.config.set_state(ConnectionState::Reconnecting { attempt });
+
+      trace!("waiting {:?} before reconnecting", delay);
+      if self.config.queue.wait_released(delay) {
+        info!("giving up reconnecting, all handles were dropped");
+        self.config.set_state(ConnectionState::Disconnected);
+        return false;
+      }
+
+      match reopen() {
+        Ok(port) => {
+          info!("serial port reconnected after {} failed attempts", attempt);
+          self.port = Some(port);
+          self.config.set_state(ConnectionState::Connected);
+          return true;
+        },
+        Err(e) => {
+          attempt += 1;
+          warn!("reconnect attempt {} failed: {}", attempt, e);
+
+          if self.config.out_of_attempts(attempt) {
+            self.config.set_state(ConnectionState::Disconnected);
+            return false;
+          }
+
+          delay = (delay * 2).min(self.config.backoff.max);
+        }
+      }
+    }
+  }
+
+  /// Sends a command to the projector, reconnecting and retrying if the port
+  /// is lost in the process. Everything sent and received is copied to
+  /// `wire`.
+  fn send(&mut self, command: &Command, wire: &mut Wire) -> CommandResult {
+    loop {
+      if self.port.is_none() && !self.reconnect() {
+        return Err(Error::Disconnected);
+      }
+
+      let port = &mut Tap { inner: self.port.as_mut().unwrap().as_mut(), wire: &mut *wire };
+      let options = &self.config.options;
+      let result = match command {
+        Command::Get(key) => send_get(port, key, options),
+        Command::Set((key, value)) => send_set(port, key, value, options),
+        Command::Action(key) => send_action(port, key, options),
+        _ => Ok(None)
+      };
+
+      match result {
+        Err(e) if e.is_disconnect() => {
+          warn!("serial port lost: {}", e);
+          self.port = None;
+
+          if !self.reconnect() {
+            // the original error is more useful if reconnection is disabled
+            return Err(if self.reopen.is_some() { Error::Disconnected } else { e });
+          }
+        },
+        result => return result
+      }
+    }
+  }
+}
+
+/// Runs the blocking engine on a new thread until it is stopped or every
+/// `ProjectorControl` handle has been dropped.
+///
+/// Once the handles are gone, commands that still have someone waiting on
+/// them are executed and the rest are dropped. The port is closed when the
+/// thread exits.
+pub(crate) fn spawn_command_thread(mut worker: Worker) {
+  thread::spawn(move || {
+    let queue = Arc::clone(&worker.config.queue);
+    let mut ready_at = Instant::now();
+
+    while let Some(cmd) = queue.blocking_pop() {
+      if worker.config.abandoned(&cmd) {
+        continue;
+      }
+
+      info!("command: {:?}", &cmd.command);
+      queue.set_current(Some(&cmd.command));
+
+      // hack: sending commands too quickly after powering on crashes the serial
+      // interface, so block the processing thread for a bit
+      // note that this does nothing to protect us if we accidentally send commands
+      // after the user presses buttons on the projector - we'll need to rely on
+      let mut delay = Duration::ZERO;
+      if !matches!(cmd.command, Command::Stop) {
+        if let Some(remaining) = remaining(ready_at) {
+          trace!("waiting {:?} before command", remaining);
+          thread::sleep(remaining);
+          delay = remaining;
+        }
+      }
+
+      let result = match &cmd.command {
+        Command::Get(_) | Command::Set(_) | Command::Action(_) => {
+          let timestamp = SystemTime::now();
+          let started = Instant::now();
+          let mut wire = Wire::default();
+
+          let result = worker.send(&cmd.command, &mut wire);
+          worker.config.journal.record(
+            wire.into_entry(timestamp, &cmd.command, &result, started.elapsed(), delay)
+          );
+
+          result
+        },
+        Command::Stop => Ok(None),
+        Command::Sleep(d) => {
+          thread::sleep(*d);
+          Ok(None)
+        }
+      };
+
+      queue.set_current(None);
+      let command = respond(cmd, result);
+      if let Command::Stop = command {
+        break;
+      }
+
+      ready_at = Instant::now() + worker.config.pacing.delay(&command);
+      queue.set_ready_at(ready_at);
+    }
+
+    debug!("command thread exiting");
+  });
+}
+
+/// Owns the port within the async engine.
+pub(crate) struct AsyncWorker {
+  pub(crate) port: Option<Box<dyn AsyncTransport>>,
+  pub(crate) reopen: Option<AsyncPortOpener>,
+  pub(crate) config: WorkerConfig,
+}
+
+impl AsyncWorker {
+  /// Async version of [`Worker::reconnect`].
+  async fn reconnect(&mut self) -> bool {
+    let reopen = match self.reopen.as_mut() {
+      Some(reopen) => reopen,
+      None => {
+        self.config.set_state(ConnectionState::Disconnected);
+        return false;
+      }
+    };
+
+    let mut delay = self.config.backoff.initial;
+    let mut attempt = 0;
+    loop {
+      self.config.set_state(ConnectionState::Reconnecting { attempt });
+
+      trace!("waiting {:?} before reconnecting", delay);
+      if rt::timeout(delay, self.config.queue.released()).await.is_some() {
+        info!("giving up reconnecting, all handles were dropped");
+        self.config.set_state(ConnectionState::Disconnected);
+        return false;
+      }
+
+      match reopen().await {
+        Ok(port) => {
+          info!("serial port reconnected after {} failed attempts", attempt);
+          self.port = Some(port);
+          self.config.set_state(ConnectionState::Connected);
+          return true;
+        },
+        Err(e) => {
+          attempt += 1;
+          warn!("reconnect attempt {} failed: {}", attempt, e);
+
+          if self.config.out_of_attempts(attempt) {
+            self.config.set_state(ConnectionState::Disconnected);
+            return false;
+          }
+
+          delay = (delay * 2).min(self.config.backoff.max);
+        }
+      }
+    }
+  }
+
+  /// Async version of [`Worker::send`].
+  async fn send(&mut self, command: &Command, wire: &mut Wire) -> CommandResult {
+    loop {
+      if self.port.is_none() && !self.reconnect().await {
+        return Err(Error::Disconnected);
+      }
+
+      let port = &mut Tap { inner: self.port.as_mut().unwrap().as_mut(), wire: &mut *wire };
+      let options = &self.config.options;
+      let result = match command {
+        Command::Get(key) => send_get_async(port, key, options).await,
+        Command::Set((key, value)) => send_set_async(port, key, value, options).await,
+        Command::Action(key) => send_action_async(port, key, options).await,
+        _ => Ok(None)
+      };
+
+      match result {
+        Err(e) if e.is_disconnect() => {
+          warn!("serial port lost: {}", e);
+          self.port = None;
+
+          if !self.reconnect().await {
+            // the original error is more useful if reconnection is disabled
+            return Err(if self.reopen.is_some() { Error::Disconnected } else { e });
+          }
+        },
+        result => return result
+      }
+    }
+  }
+}
+
+async fn run_commands(mut worker: AsyncWorker) {
+  let queue = Arc::clone(&worker.config.queue);
+  let mut ready_at = Instant::now();
+
+  while let Some(cmd) = queue.pop().await {
+    if worker.config.abandoned(&cmd) {
+      continue;
+    }
+
+    info!("command: {:?}", &cmd.command);
+    queue.set_current(Some(&cmd.command));
+
+    // see spawn_command_thread()
+    let mut delay = Duration::ZERO;
+    if !matches!(cmd.command, Command::Stop) {
+      if let Some(remaining) = remaining(ready_at) {
+        trace!("waiting {:?} before command", remaining);
+        rt::sleep(remaining).await;
+        delay = remaining;
+      }
+    }
+
+    let result = match &cmd.command {
+      Command::Get(_) | Command::Set(_) | Command::Action(_) => {
+        let timestamp = SystemTime::now();
+        let started = Instant::now();
+        let mut wire = Wire::default();
+
+        let result = worker.send(&cmd.command, &mut wire).await;
+        worker.config.journal.record(
+          wire.into_entry(timestamp, &cmd.command, &result, started.elapsed(), delay)
+        );
+
+        result
+      },
+      Command::Stop => Ok(None),
+      Command::Sleep(d) => {
+        rt::sleep(*d).await;
+        Ok(None)
+      }
+    };
+
+    queue.set_current(None);
+    let command = respond(cmd, result);
+    if let Command::Stop = command {
+      break;
+    }
+
+    ready_at = Instant::now() + worker.config.pacing.delay(&command);
+    queue.set_ready_at(ready_at);
+  }
+
+  debug!("command engine exiting");
+}
+
+/// The async command engine, which must be spawned on (or otherwise polled by)
+/// an executor for commands to be processed.
+///
+/// It completes once the engine has stopped, either via
+/// [`ProjectorControl::stop`](crate::ProjectorControl::stop) or because every
+/// `ProjectorControl` handle was dropped. Dropping it cancels processing
+/// immediately, including any in-progress sleep or read.
+#[must_use = "the command engine does nothing unless spawned"]
+pub struct CommandEngine(BoxFuture<'static, ()>);
+
+impl CommandEngine {
+  pub(crate) fn new(worker: AsyncWorker) -> Self {
+    CommandEngine(run_commands(worker).boxed())
+  }
+}
+
+impl Future for CommandEngine {
+  type Output = ();
+
+  fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+    self.0.poll_unpin(cx)
+  }
+}
@@ -0,0 +1,210 @@
+//! Controlling several projectors as one, e.g. a stacked pair.
+//!
+//! A [`ProjectorGroup`] sends each command to every member at once and
+//! collects the results per member. Each member keeps its own queue and
+//! pacing, so a slow projector doesn't hold up the others.
+//!
+//! ```no_run
+//! use benq_control::{PowerState, ProjectorControl};
+//! use benq_control::detect::LineSettings;
+//! use benq_control::group::ProjectorGroup;
+//! # use std::time::Duration;
+//!
+//! # async fn example() -> benq_control::Result<()> {
+//! let open = |path: &str| LineSettings::new(115_200).open(path, Duration::from_millis(50));
+//! let group = ProjectorGroup::new()
+//!   .with_member("left", ProjectorControl::new(open("/dev/ttyUSB0")?))
+//!   .with_member("right", ProjectorControl::new(open("/dev/ttyUSB1")?));
+//!
+//! group.set_power(PowerState::On).await?;
+//! assert_eq!(group.power().await?, PowerState::On);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt;
+use std::future::Future;
+
+use futures::future;
+
+use crate::{Command, Error, PowerState, ProjectorControl, Result, Source};
+
+/// A set of named projectors controlled together. See the
+/// [module docs](self).
+#[derive(Clone, Default)]
+pub struct ProjectorGroup {
+  members: Vec<(String, ProjectorControl)>,
+}
+
+impl ProjectorGroup {
+  pub fn new() -> ProjectorGroup {
+    ProjectorGroup::default()
+  }
+
+  /// Adds a member. Names are used to report per-member results.
+  pub fn with_member(mut self, name: impl Into<String>, projector: ProjectorControl) -> Self {
+    self.push(name, projector);
+    self
+  }
+
+  pub fn push(&mut self, name: impl Into<String>, projector: ProjectorControl) {
+    self.members.push((name.into(), projector));
+  }
+
+  pub fn members(&self) -> impl Iterator<Item = (&str, &ProjectorControl)> {
+    self.members.iter().map(|(name, projector)| (name.as_str(), projector))
+  }
+
+  pub fn len(&self) -> usize {
+    self.members.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.members.is_empty()
+  }
+
+  /// Runs `f` against every member concurrently and collects the results.
+  pub async fn run<'a, F, Fut, T>(&'a self, f: F) -> GroupResults<T>
+  where
+    F: Fn(&'a ProjectorControl) -> Fut,
+    Fut: Future<Output = Result<T>>
+  {
+    let results = future::join_all(self.members.iter().map(|(_, p)| f(p))).await;
+
+    GroupResults {
+      results: self.members.iter().map(|(name, _)| name.clone()).zip(results).collect()
+    }
+  }
+
+  /// Executes a command on every member.
+  pub async fn submit_command(&self, command: impl Into<Command>) -> GroupResults<Option<String>> {
+    let command = command.into();
+    self.run(|p| p.submit_command(command.clone())).await
+  }
+
+  /// Queries the power state, failing unless every member agrees.
+  pub async fn power(&self) -> Result<PowerState> {
+    self.run(|p| p.power()).await.agreed()
+  }
+
+  /// Turns every member on or off.
+  pub async fn set_power(&self, state: PowerState) -> Result<()> {
+    self.run(|p| p.set_power(state)).await.all_ok()
+  }
+
+  /// Queries the input source, failing unless every member agrees.
+  pub async fn source(&self) -> Result<Source> {
+    self.run(|p| p.source()).await.agreed()
+  }
+
+  /// Switches every member to the given input source.
+  pub async fn set_source(&self, source: &Source) -> Result<()> {
+    self.run(|p| p.set_source(source)).await.all_ok()
+  }
+
+  /// Queries the volume, failing unless every member agrees.
+  pub async fn volume(&self) -> Result<u8> {
+    self.run(|p| p.volume()).await.agreed()
+  }
+
+  /// Sets the volume on every member.
+  pub async fn set_volume(&self, volume: u8) -> Result<()> {
+    self.run(|p| p.set_volume(volume)).await.all_ok()
+  }
+
+  /// Queries whether audio is muted, failing unless every member agrees.
+  pub async fn muted(&self) -> Result<bool> {
+    self.run(|p| p.muted()).await.agreed()
+  }
+
+  /// Mutes or unmutes every member.
+  pub async fn set_muted(&self, muted: bool) -> Result<()> {
+    self.run(|p| p.set_muted(muted)).await.all_ok()
+  }
+
+  /// Queries whether the screen is blanked, failing unless every member
+  /// agrees.
+  pub async fn blanked(&self) -> Result<bool> {
+    self.run(|p| p.blanked()).await.agreed()
+  }
+
+  /// Blanks or unblanks every member.
+  pub async fn set_blank(&self, blank: bool) -> Result<()> {
+    self.run(|p| p.set_blank(blank)).await.all_ok()
+  }
+}
+
+/// Per-member results of a group operation, in member order.
+#[derive(Debug)]
+pub struct GroupResults<T> {
+  pub results: Vec<(String, Result<T>)>,
+}
+
+impl<T> GroupResults<T> {
+  /// Returns true if every member succeeded.
+  pub fn is_ok(&self) -> bool {
+    self.results.iter().all(|(_, r)| r.is_ok())
+  }
+
+  /// Returns the members that failed, with their errors.
+  pub fn failures(&self) -> impl Iterator<Item = (&str, &Error)> {
+    self.results.iter().filter_map(|(name, r)| r.as_ref().err().map(|e| (name.as_str(), e)))
+  }
+
+  /// Returns every member's value, or [`Error::GroupFailed`] listing each
+  /// member that failed.
+  pub fn into_result(self) -> Result<Vec<(String, T)>> {
+    let total = self.results.len();
+    let mut values = Vec::with_capacity(total);
+    let mut failed = Vec::new();
+
+    for (name, result) in self.results {
+      match result {
+        Ok(value) => values.push((name, value)),
+        Err(e) => failed.push((name, e)),
+      }
+    }
+
+    if failed.is_empty() {
+      Ok(values)
+    } else {
+      Err(Error::GroupFailed { total, failed })
+    }
+  }
+
+  /// Like [`into_result`](Self::into_result), discarding the values.
+  pub fn all_ok(self) -> Result<()> {
+    self.into_result().map(|_| ())
+  }
+
+  /// Returns the value every member reported, [`Error::GroupFailed`] if any
+  /// member failed, or [`Error::GroupDisagrees`] if they differ. Fails with
+  /// [`Error::GroupEmpty`] if there are no members.
+  pub fn agreed(self) -> Result<T>
+  where
+    T: PartialEq + fmt::Display
+  {
+    let values = self.into_result()?;
+
+    let first = match values.first() {
+      Some((_, first)) => first,
+      None => return Err(Error::GroupEmpty)
+    };
+
+    if values.iter().all(|(_, value)| value == first) {
+      Ok(values.into_iter().next().unwrap().1)
+    } else {
+      Err(Error::GroupDisagrees {
+        values: values.into_iter().map(|(name, value)| (name, value.to_string())).collect()
+      })
+    }
+  }
+}
+
+/// Formats per-member errors or values as `name: value, name: value`.
+pub(crate) fn describe<T: fmt::Display>(members: &[(String, T)]) -> String {
+  members.iter()
+    .map(|(name, value)| format!("{}: {}", name, value))
+    .collect::<Vec<_>>()
+    .join(", ")
+}
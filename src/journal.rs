@@ -0,0 +1,188 @@
+//! An in-memory journal of recent exchanges with the projector.
+//!
+//! Each executed command is recorded along with the raw bytes that crossed the
+//! wire, which is usually enough to diagnose a misbehaving projector without
+//! enabling trace logging.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use futures::FutureExt;
+use futures::future::BoxFuture;
+use serialport::ClearBuffer;
+
+use crate::{Command, CommandResult, Result};
+use crate::protocol::{AsyncTransport, Transport};
+
+/// The default number of entries kept in the journal.
+pub const DEFAULT_JOURNAL_CAPACITY: usize = 64;
+
+/// A single command execution.
+#[derive(Debug, Clone)]
+pub struct JournalEntry {
+  /// When the command was sent
+  pub timestamp: SystemTime,
+
+  pub command: Command,
+
+  /// Everything written to the port, including wake-up line endings
+  pub written: Vec<u8>,
+
+  /// Everything read from the port, including echoes and noise
+  pub read: Vec<u8>,
+
+  /// The parsed response, or the error's message
+  pub result: std::result::Result<Option<String>, String>,
+
+  /// How long the exchange took
+  pub duration: Duration,
+
+  /// How long the command was held back to pace it after the previous one
+  pub delay: Duration,
+}
+
+impl fmt::Display for JournalEntry {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let since_epoch = self.timestamp.duration_since(UNIX_EPOCH).unwrap_or_default();
+
+    write!(
+      f,
+      "[{}.{:03}] {:?} after {:?} took {:?}: ",
+      since_epoch.as_secs(),
+      since_epoch.subsec_millis(),
+      self.command,
+      self.delay,
+      self.duration
+    )?;
+
+    match &self.result {
+      Ok(Some(response)) => writeln!(f, "{}", response)?,
+      Ok(None) => writeln!(f, "(no response)")?,
+      Err(e) => writeln!(f, "error: {}", e)?,
+    }
+
+    writeln!(f, "  > {}", self.written.escape_ascii())?;
+    write!(f, "  < {}", self.read.escape_ascii())
+  }
+}
+
+/// A bounded buffer of the most recent journal entries.
+pub(crate) struct Journal {
+  entries: Mutex<VecDeque<JournalEntry>>,
+  capacity: usize,
+}
+
+impl Journal {
+  pub(crate) fn new(capacity: usize) -> Journal {
+    Journal {
+      entries: Mutex::new(VecDeque::with_capacity(capacity)),
+      capacity,
+    }
+  }
+
+  pub(crate) fn record(&self, entry: JournalEntry) {
+    if self.capacity == 0 {
+      return;
+    }
+
+    let mut entries = self.entries.lock().unwrap();
+    if entries.len() >= self.capacity {
+      entries.pop_front();
+    }
+
+    entries.push_back(entry);
+  }
+
+  /// Returns all entries, oldest first.
+  pub(crate) fn entries(&self) -> Vec<JournalEntry> {
+    self.entries.lock().unwrap().iter().cloned().collect()
+  }
+}
+
+/// Bytes captured during one exchange.
+#[derive(Debug, Default)]
+pub(crate) struct Wire {
+  pub(crate) written: Vec<u8>,
+  pub(crate) read: Vec<u8>,
+}
+
+impl Wire {
+  pub(crate) fn into_entry(
+    self,
+    timestamp: SystemTime,
+    command: &Command,
+    result: &CommandResult,
+    duration: Duration,
+    delay: Duration
+  ) -> JournalEntry {
+    JournalEntry {
+      timestamp,
+      command: command.clone(),
+      written: self.written,
+      read: self.read,
+      result: match result {
+        Ok(response) => Ok(response.clone()),
+        Err(e) => Err(e.to_string()),
+      },
+      duration,
+      delay,
+    }
+  }
+}
+
+/// Wraps a transport, copying everything read and written into a [`Wire`].
+pub(crate) struct Tap<'a, T: ?Sized> {
+  pub(crate) inner: &'a mut T,
+  pub(crate) wire: &'a mut Wire,
+}
+
+impl<T: Read + ?Sized> Read for Tap<'_, T> {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    let n = self.inner.read(buf)?;
+    self.wire.read.extend_from_slice(&buf[..n]);
+    Ok(n)
+  }
+}
+
+impl<T: Write + ?Sized> Write for Tap<'_, T> {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    let n = self.inner.write(buf)?;
+    self.wire.written.extend_from_slice(&buf[..n]);
+    Ok(n)
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    self.inner.flush()
+  }
+}
+
+impl<T: Transport + ?Sized> Transport for Tap<'_, T> {
+  fn clear(&mut self, buffer: ClearBuffer) -> Result<()> {
+    self.inner.clear(buffer)
+  }
+}
+
+impl<T: AsyncTransport + ?Sized> AsyncTransport for Tap<'_, T> {
+  fn read<'a>(&'a mut self, buf: &'a mut [u8]) -> BoxFuture<'a, io::Result<usize>> {
+    async move {
+      let n = self.inner.read(&mut *buf).await?;
+      self.wire.read.extend_from_slice(&buf[..n]);
+      Ok(n)
+    }.boxed()
+  }
+
+  fn write_all<'a>(&'a mut self, buf: &'a [u8]) -> BoxFuture<'a, io::Result<()>> {
+    async move {
+      self.inner.write_all(buf).await?;
+      self.wire.written.extend_from_slice(buf);
+      Ok(())
+    }.boxed()
+  }
+
+  fn clear(&mut self, buffer: ClearBuffer) -> Result<()> {
+    self.inner.clear(buffer)
+  }
+}
@@ -1,18 +1,78 @@
+use std::fmt;
 use std::future::Future;
-use std::io::{self, Read};
-use std::str;
-use std::thread::{self, JoinHandle};
+use std::io;
+use std::str::{self, FromStr};
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 
 use futures::FutureExt;
 use futures::channel::oneshot;
 use futures::future::{self, BoxFuture};
-use log::{trace, debug, info, warn};
-use serialport::{SerialPort, ClearBuffer};
+use log::debug;
 use thiserror::Error;
-use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
 
-const RESPONSE_WAIT_PERIOD: Duration = Duration::from_millis(200);
+/// Implements `Serialize` and `Deserialize` (with the `serde` feature) for
+/// types that round-trip through `Display` and `FromStr`, so they use the same
+/// strings as the projector and the command line.
+macro_rules! serde_via_str {
+  ($($name:ty),* $(,)?) => {$(
+    #[cfg(feature = "serde")]
+    impl serde::Serialize for $name {
+      fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+      where
+        S: serde::Serializer
+      {
+        serializer.collect_str(self)
+      }
+    }
+
+    #[cfg(feature = "serde")]
+    impl<'de> serde::Deserialize<'de> for $name {
+      fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+      where
+        D: serde::Deserializer<'de>
+      {
+        <String as serde::Deserialize>::deserialize(deserializer)?
+          .parse()
+          .map_err(serde::de::Error::custom)
+      }
+    }
+  )*};
+}
+
+pub mod blocking;
+pub mod detect;
+pub mod discovery;
+mod engine;
+pub mod group;
+mod journal;
+pub mod profile;
+pub mod protocol;
+mod queue;
+mod rt;
+pub mod sequence;
+pub mod snapshot;
+#[cfg(feature = "testing")]
+pub mod testing;
+pub mod transcript;
+pub mod values;
+
+pub use engine::{AsyncPortOpener, CommandEngine, PortOpener};
+use engine::{spawn_command_thread, AsyncWorker, SubmittedCommand, Worker, WorkerConfig};
+use protocol::{AsyncTransport, ProtocolOptions, Transport};
+pub use journal::{JournalEntry, DEFAULT_JOURNAL_CAPACITY};
+use journal::Journal;
+pub use queue::{ActiveCommand, QueuePolicy, QueueStatus};
+use queue::{Push, Queue, SendSlot};
+pub use profile::ModelProfile;
+pub use sequence::{Sequence, SequenceReport};
+use sequence::{OnError, Step, StepOutcome, StepReport};
+pub use snapshot::{RestoreReport, SettingDiff, Snapshot};
+pub use values::{
+  AspectRatio, ColorTemperature, Gamma, KeystoneAxis, LampMode, Language, MaintenanceStatus,
+  PictureLevel, PictureMode, PowerState, ProjectorPosition, RemoteKey, Source, ThreeDMode
+};
+use values::{parse_response, parse_switch, response_value, switch};
 
 #[derive(Error, Debug)]
 pub enum Error {
@@ -52,12 +112,215 @@ pub enum Error {
   ResponseUnexpectedFormat(String),
 
   #[error("projector returned an error ('Block item')")]
-  ResponseBlockItem
+  ResponseBlockItem,
+
+  #[error("invalid protocol option: {0}")]
+  InvalidProtocolOption(String),
+
+  #[error("invalid baud rate: {0}")]
+  InvalidBaudRate(String),
+
+  #[error("no projector responded on {} with any supported line settings", path)]
+  DetectionFailed {
+    /// The serial port path that was probed
+    path: String,
+  },
+
+  #[error("invalid port selector: {0}")]
+  InvalidPortSelector(String),
+
+  #[error("no serial port matched {}", selector)]
+  NoMatchingPort {
+    /// The selector that found no ports
+    selector: String,
+  },
+
+  #[error("serial port is disconnected")]
+  Disconnected,
+
+  #[error("invalid value: {0}")]
+  InvalidValue(String),
+
+  #[error("timed out waiting for the projector")]
+  Timeout,
+
+  #[error("command queue is full: {:?}", command)]
+  QueueFull {
+    /// The command that was rejected
+    command: Command,
+  },
+
+  #[error("command was evicted from a full queue: {:?}", command)]
+  Evicted {
+    command: Command,
+  },
+
+  #[error("invalid transcript line: {0}")]
+  InvalidTranscript(String),
+
+  #[error("invalid command: {0:?}")]
+  InvalidCommand(String),
+
+  #[error("{} does not support {}={}", model, key, value)]
+  Unsupported {
+    model: String,
+    key: String,
+    value: String,
+  },
+
+  #[error("{} of {} projectors failed: {}", failed.len(), total, group::describe(failed))]
+  GroupFailed {
+    /// The number of projectors in the group
+    total: usize,
+
+    /// Each projector that failed, by name
+    failed: Vec<(String, Error)>,
+  },
+
+  #[error("projectors disagree: {}", group::describe(values))]
+  GroupDisagrees {
+    /// The value each projector reported, by name
+    values: Vec<(String, String)>,
+  },
+
+  #[error("projector group has no members")]
+  GroupEmpty,
+}
+
+impl Error {
+  /// Returns a stable, machine-readable name for the kind of error, e.g.
+  /// `block_item`. Unlike the messages, codes won't change between releases.
+  pub fn code(&self) -> &'static str {
+    match self {
+      Error::Cancelled { .. } => "cancelled",
+      Error::CommandSendError { .. } => "send_failed",
+      Error::SerialError { .. } => "serial_error",
+      Error::SerialIOError { .. } => "serial_io_error",
+      Error::CommandSendInvalidState => "no_prompt",
+      Error::ResponseInvalidString { .. } => "invalid_response_string",
+      Error::ResponseUnexpectedFormat(_) => "unexpected_response",
+      Error::ResponseBlockItem => "block_item",
+      Error::InvalidProtocolOption(_) => "invalid_protocol_option",
+      Error::InvalidBaudRate(_) => "invalid_baud_rate",
+      Error::DetectionFailed { .. } => "detection_failed",
+      Error::InvalidPortSelector(_) => "invalid_port_selector",
+      Error::NoMatchingPort { .. } => "no_matching_port",
+      Error::Disconnected => "disconnected",
+      Error::InvalidValue(_) => "invalid_value",
+      Error::Timeout => "timeout",
+      Error::QueueFull { .. } => "queue_full",
+      Error::Evicted { .. } => "evicted",
+      Error::InvalidTranscript(_) => "invalid_transcript",
+      Error::InvalidCommand(_) => "invalid_command",
+      Error::Unsupported { .. } => "unsupported",
+      Error::GroupFailed { .. } => "group_failed",
+      Error::GroupDisagrees { .. } => "group_disagrees",
+      Error::GroupEmpty => "group_empty",
+    }
+  }
+
+  /// Returns true for errors the projector produces while busy, e.g. warming
+  /// up, which may succeed if the command is retried later.
+  fn is_transient(&self) -> bool {
+    matches!(
+      self,
+      Error::ResponseBlockItem
+        | Error::CommandSendInvalidState
+        | Error::ResponseUnexpectedFormat(_)
+        | Error::ResponseInvalidString { .. }
+        | Error::SerialIOError { .. }
+    )
+  }
+
+  /// Returns `true` if this error indicates the serial port has gone away,
+  /// e.g. because a USB adapter was unplugged.
+  pub fn is_disconnect(&self) -> bool {
+    fn io_disconnect(e: &io::Error) -> bool {
+      // EIO, ENXIO and ENODEV are returned for unplugged USB adapters
+      #[cfg(unix)]
+      if let Some(5) | Some(6) | Some(19) = e.raw_os_error() {
+        return true;
+      }
+
+      matches!(
+        e.kind(),
+        io::ErrorKind::BrokenPipe | io::ErrorKind::NotConnected | io::ErrorKind::UnexpectedEof
+      )
+    }
+
+    match self {
+      Error::Disconnected => true,
+      Error::SerialIOError { source } => io_disconnect(source),
+      Error::SerialError { source } => match source.kind() {
+        serialport::ErrorKind::NoDevice => true,
+        serialport::ErrorKind::Io(kind) => matches!(
+          kind,
+          io::ErrorKind::BrokenPipe | io::ErrorKind::NotConnected | io::ErrorKind::UnexpectedEof
+        ),
+        _ => false
+      },
+      _ => false
+    }
+  }
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
 
-#[derive(Debug, Clone)]
+/// An [`Error`](enum@Error) reduced to its code and message, e.g. to send over the
+/// network. With the `serde` feature, `Error` itself serializes to this.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ErrorInfo {
+  /// See [`Error::code`]
+  pub code: String,
+
+  pub message: String,
+}
+
+impl From<&Error> for ErrorInfo {
+  fn from(e: &Error) -> Self {
+    ErrorInfo {
+      code: e.code().to_string(),
+      message: e.to_string(),
+    }
+  }
+}
+
+impl fmt::Display for ErrorInfo {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{} ({})", self.message, self.code)
+  }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Error {
+  fn serialize<S: serde::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+    ErrorInfo::from(self).serialize(serializer)
+  }
+}
+
+/// A [`CommandResult`] in a form that can be serialized, as
+/// `{"response": ...}` or `{"error": {"code": ..., "message": ...}}`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum CommandOutcome {
+  /// The projector's response, if there was one
+  Response(Option<String>),
+
+  Error(ErrorInfo),
+}
+
+impl From<CommandResult> for CommandOutcome {
+  fn from(result: CommandResult) -> Self {
+    match result {
+      Ok(response) => CommandOutcome::Response(response),
+      Err(e) => CommandOutcome::Error(ErrorInfo::from(&e)),
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Command {
   /// A special pseudo-command to end the processing thread
   Stop,
@@ -68,6 +331,9 @@ pub enum Command {
   /// A setter command changes the projector's state
   Set((String, String)),
 
+  /// A bare action with no value, such as a remote key press (`*up#`)
+  Action(String),
+
   /// A special command to sleep the processing thread.
   ///
   /// This is intended to work around potential serial interface crashes when
@@ -100,221 +366,1068 @@ impl From<(&str, String)> for Command {
   }
 }
 
+impl Command {
+  /// Checks that this command can be sent safely: keys must be ASCII
+  /// alphanumeric and values printable ASCII, without the `*`, `#`, `=` or
+  /// `?` characters that delimit frames. Anything else could be used to
+  /// smuggle extra commands onto the wire.
+  pub fn validate(&self) -> Result<()> {
+    match self {
+      Command::Get(key) | Command::Action(key) => check_key(key),
+      Command::Set((key, value)) => check_key(key).and_then(|_| check_value(value)),
+      Command::Stop | Command::Sleep(_) => Ok(())
+    }
+  }
+}
+
+pub(crate) fn check_key(key: &str) -> Result<()> {
+  if key.is_empty() || !key.bytes().all(|b| b.is_ascii_alphanumeric()) {
+    return Err(Error::InvalidCommand(key.to_string()));
+  }
+
+  Ok(())
+}
+
+pub(crate) fn check_value(value: &str) -> Result<()> {
+  let valid = |b: u8| b.is_ascii_graphic() && !matches!(b, b'*' | b'#' | b'=' | b'?');
+  if value.is_empty() || !value.bytes().all(valid) {
+    return Err(Error::InvalidCommand(value.to_string()));
+  }
+
+  Ok(())
+}
+
+/// Formats commands as they are written on the wire, minus the `*...#`
+/// frame: `key=?` for queries and `key=value` for everything else. Actions are
+/// formatted as `key!` to tell them apart from queries.
+impl fmt::Display for Command {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Command::Stop => write!(f, "(stop)"),
+      Command::Get(key) => write!(f, "{}=?", key),
+      Command::Set((key, value)) => write!(f, "{}={}", key, value),
+      Command::Action(key) => write!(f, "{}!", key),
+      Command::Sleep(duration) => write!(f, "(sleep {:?})", duration),
+    }
+  }
+}
+
+/// Parses `key=?` (or just `key`) as a query, `key=value` as a set command
+/// and `key!` as an action, ignoring surrounding whitespace. Keys are
+/// lowercased, since the projector accepts them in any case, but values are
+/// kept as given. The result is [validated](Command::validate).
+impl FromStr for Command {
+  type Err = Error;
+
+  fn from_str(s: &str) -> Result<Self> {
+    let command = match s.trim().split_once('=') {
+      None => match s.trim().strip_suffix('!') {
+        Some(key) => Command::Action(key.to_ascii_lowercase()),
+        None => Command::Get(s.trim().to_ascii_lowercase()),
+      },
+      Some((key, "?")) => Command::Get(key.to_ascii_lowercase()),
+      Some((key, value)) => Command::Set((key.to_ascii_lowercase(), value.to_string())),
+    };
+
+    match command.validate() {
+      Ok(()) => Ok(command),
+      Err(_) => Err(Error::InvalidCommand(s.to_string()))
+    }
+  }
+}
+
+/// Serializes as e.g. `pow=on`. `Stop` and `Sleep` only ever come from the
+/// library itself and have no such syntax, so serializing them fails.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Command {
+  fn serialize<S: serde::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+    match self {
+      Command::Stop | Command::Sleep(_) => Err(serde::ser::Error::custom(
+        format!("{} is internal and can't be serialized", self)
+      )),
+      _ => serializer.collect_str(self),
+    }
+  }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Command {
+  fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+    <String as serde::Deserialize>::deserialize(deserializer)?
+      .parse()
+      .map_err(serde::de::Error::custom)
+  }
+}
+
 pub type CommandResult = Result<Option<String>>;
 
-#[derive(Debug)]
-struct SubmittedCommand {
-  command: Command,
-  tx: oneshot::Sender<CommandResult>
+/// The state of the connection to the projector.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionState {
+  Connected,
+
+  /// The port was lost and is being reopened. Commands are held in the queue
+  /// until it succeeds.
+  Reconnecting {
+    /// The number of failed attempts so far
+    attempt: u32
+  },
+
+  /// The port was lost and couldn't be reopened, either because no means of
+  /// reopening it was configured or because
+  /// [`ReconnectBackoff::max_attempts`] ran out. Commands fail with
+  /// [`Error::Disconnected`], though with reconnection enabled each one tries
+  /// to reopen the port again first.
+  Disconnected,
+}
+
+impl fmt::Display for ConnectionState {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      ConnectionState::Connected => write!(f, "connected"),
+      ConnectionState::Reconnecting { .. } => write!(f, "reconnecting"),
+      ConnectionState::Disconnected => write!(f, "disconnected"),
+    }
+  }
+}
+
+/// Backoff between attempts to reopen a lost serial port.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReconnectBackoff {
+  /// Delay before the first attempt; doubled after each failure
+  pub initial: Duration,
+
+  /// Upper bound for the delay between attempts
+  pub max: Duration,
+
+  /// Give up after this many failed attempts, failing the command that was
+  /// executing with [`Error::Disconnected`]; `None` (the default) retries
+  /// until the port comes back or every handle is dropped
+  pub max_attempts: Option<u32>,
+}
+
+impl Default for ReconnectBackoff {
+  fn default() -> Self {
+    ReconnectBackoff {
+      initial: Duration::from_secs(1),
+      max: Duration::from_secs(60),
+      max_attempts: None,
+    }
+  }
+}
+
+/// Delays after each command before the next is sent.
+///
+/// Sending commands too quickly after powering on or off crashes the serial
+/// interface of some projectors, so the defaults are conservative. They may be
+/// shortened for projectors known to cope, or disabled entirely when testing
+/// against a mock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pacing {
+  /// Delay after `pow=on`
+  pub power_on: Duration,
+
+  /// Delay after `pow=off`, which takes longer
+  pub power_off: Duration,
+
+  /// Delay after any other set command
+  pub set: Duration,
+
+  /// Delay after a query (or a sleep)
+  pub get: Duration,
+
+  /// Delay after an action such as a remote key press, so that the on-screen
+  /// menu can keep up
+  pub key_press: Duration,
+}
+
+impl Default for Pacing {
+  fn default() -> Self {
+    Pacing {
+      power_on: Duration::from_secs(30),
+      power_off: Duration::from_secs(60),
+      set: Duration::from_millis(500),
+      get: Duration::from_millis(1),
+      key_press: Duration::from_millis(300),
+    }
+  }
+}
+
+impl Pacing {
+  /// No delays at all.
+  pub fn none() -> Pacing {
+    Pacing {
+      power_on: Duration::ZERO,
+      power_off: Duration::ZERO,
+      set: Duration::ZERO,
+      get: Duration::ZERO,
+      key_press: Duration::ZERO,
+    }
+  }
+
+  /// Returns how long to wait after executing `command` before sending
+  /// anything else.
+  pub fn delay(&self, command: &Command) -> Duration {
+    match command {
+      Command::Set((k, v)) if k.eq_ignore_ascii_case("pow") => {
+        if v.eq_ignore_ascii_case("off") {
+          self.power_off
+        } else {
+          self.power_on
+        }
+      },
+      Command::Set(_) => self.set,
+      Command::Action(_) => self.key_press,
+      _ => self.get
+    }
+  }
+}
+
+/// How [`ProjectorControl::power_on_and_wait`] and
+/// [`ProjectorControl::power_off_and_wait`] poll for the projector to finish
+/// changing state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PowerWait {
+  /// Give up with [`Error::Timeout`] after this long
+  pub timeout: Duration,
+
+  /// Delay between polls once the [`Pacing`] delay after the power command
+  /// has passed
+  pub poll_interval: Duration,
+}
+
+impl Default for PowerWait {
+  fn default() -> Self {
+    PowerWait {
+      timeout: Duration::from_secs(180),
+      poll_interval: Duration::from_secs(2),
+    }
+  }
+}
+
+/// Progress reported while waiting for a power change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PowerProgress {
+  /// The power command was accepted, or the projector was already in the
+  /// target state
+  Sent,
+
+  /// The projector isn't ready yet
+  Waiting {
+    elapsed: Duration,
+
+    /// The power state last reported, if the projector answered at all
+    power: Option<PowerState>,
+  },
+
+  /// The projector reached the target state and is accepting commands
+  Ready {
+    elapsed: Duration,
+  },
+}
+
+/// Builds a [`ProjectorControl`] with non-default options.
+#[derive(Default)]
+pub struct ProjectorControlBuilder {
+  protocol: ProtocolOptions,
+  reopen: Option<PortOpener>,
+  reopen_async: Option<AsyncPortOpener>,
+  backoff: ReconnectBackoff,
+  pacing: Pacing,
+  queue_capacity: Option<usize>,
+  queue_policy: QueuePolicy,
+  journal_capacity: Option<usize>,
+  profile: ModelProfile,
+}
+
+/// The default number of commands that may wait in the queue.
+pub const DEFAULT_QUEUE_CAPACITY: usize = 64;
+
+impl ProjectorControlBuilder {
+  /// Sets protocol options to match the projector's firmware.
+  pub fn protocol(mut self, options: ProtocolOptions) -> Self {
+    self.protocol = options;
+    self
+  }
+
+  /// Enables automatic reconnection, using `reopen` to reopen the port if it
+  /// disappears (e.g. a USB adapter is unplugged).
+  ///
+  /// Queued commands are held until the port is reopened; the command that
+  /// was executing when the port was lost is retried. Reconnection stops once
+  /// every handle has been dropped (e.g. by [`ProjectorControl::shutdown`]) or
+  /// [`ReconnectBackoff::max_attempts`] is reached.
+  pub fn reconnect<F, T>(mut self, mut reopen: F) -> Self
+  where
+    F: FnMut() -> Result<T> + Send + 'static,
+    T: Transport + 'static
+  {
+    self.reopen = Some(Box::new(move || {
+      reopen().map(|port| Box::new(port) as Box<dyn Transport>)
+    }));
+    self
+  }
+
+  /// Like [`reconnect`](Self::reconnect), but for the async engine started by
+  /// [`build_async`](Self::build_async).
+  pub fn reconnect_async<F, Fut, T>(mut self, mut reopen: F) -> Self
+  where
+    F: FnMut() -> Fut + Send + 'static,
+    Fut: Future<Output = Result<T>> + Send + 'static,
+    T: AsyncTransport + 'static
+  {
+    self.reopen_async = Some(Box::new(move || {
+      reopen().map(|r| r.map(|port| Box::new(port) as Box<dyn AsyncTransport>)).boxed()
+    }));
+    self
+  }
+
+  /// Sets the backoff between reconnection attempts.
+  pub fn reconnect_backoff(mut self, backoff: ReconnectBackoff) -> Self {
+    self.backoff = backoff;
+    self
+  }
+
+  /// Sets the delays between commands.
+  pub fn pacing(mut self, pacing: Pacing) -> Self {
+    self.pacing = pacing;
+    self
+  }
+
+  /// Sets the model profile that typed setters are validated against (by
+  /// default a permissive [`ModelProfile::generic`] profile). See also
+  /// [`ProjectorControl::detect_profile`].
+  pub fn profile(mut self, profile: ModelProfile) -> Self {
+    self.profile = profile;
+    self
+  }
+
+  /// Sets how many commands may wait in the queue (default
+  /// [`DEFAULT_QUEUE_CAPACITY`], and at least 1), and what happens when it is
+  /// full.
+  pub fn queue(mut self, capacity: usize, policy: QueuePolicy) -> Self {
+    // with no room at all, every command would wait or be rejected forever
+    self.queue_capacity = Some(capacity.max(1));
+    self.queue_policy = policy;
+    self
+  }
+
+  /// Sets how many recent exchanges are kept in the journal (default
+  /// [`DEFAULT_JOURNAL_CAPACITY`]); 0 disables it.
+  pub fn journal(mut self, capacity: usize) -> Self {
+    self.journal_capacity = Some(capacity);
+    self
+  }
+
+  /// Creates a handle along with the config for its worker.
+  fn handle(&self) -> (ProjectorControl, WorkerConfig) {
+    let (done_tx, done_rx) = oneshot::channel();
+    let state = Arc::new(Mutex::new(ConnectionState::Connected));
+    let queue = Arc::new(Queue::new(
+      self.queue_capacity.unwrap_or(DEFAULT_QUEUE_CAPACITY),
+      self.queue_policy
+    ));
+    let journal = Arc::new(Journal::new(
+      self.journal_capacity.unwrap_or(DEFAULT_JOURNAL_CAPACITY)
+    ));
+
+    let config = WorkerConfig {
+      backoff: self.backoff,
+      pacing: self.pacing,
+      options: self.protocol,
+      state: Arc::clone(&state),
+      queue: Arc::clone(&queue),
+      journal: Arc::clone(&journal),
+      _done: done_tx,
+    };
+
+    let handle = ProjectorControl {
+      shared: Arc::new(Shared {
+        queue,
+        journal,
+        state,
+        profile: Mutex::new(self.profile.clone()),
+        done: done_rx.shared(),
+      })
+    };
+
+    (handle, config)
+  }
+
+  /// Starts processing commands on the given port using a dedicated
+  /// background thread.
+  pub fn build<T: Transport + 'static>(self, port: T) -> ProjectorControl {
+    let (handle, config) = self.handle();
+
+    let worker = Worker {
+      port: Some(Box::new(port)),
+      reopen: self.reopen,
+      config,
+    };
+    spawn_command_thread(worker);
+
+    handle
+  }
+
+  /// Creates a controller for the given async port, returning it along with
+  /// the [`CommandEngine`] that processes its commands.
+  ///
+  /// The engine must be spawned by the caller, e.g. with `tokio::spawn()`. It
+  /// doesn't need a thread of its own, so many projectors can share a single
+  /// executor thread.
+  pub fn build_async<T>(self, port: T) -> (ProjectorControl, CommandEngine)
+  where
+    T: AsyncTransport + 'static
+  {
+    let (handle, config) = self.handle();
+
+    let worker = AsyncWorker {
+      port: Some(Box::new(port)),
+      reopen: self.reopen_async,
+      config,
+    };
+
+    (handle, CommandEngine::new(worker))
+  }
+
+  /// Like [`build`](Self::build), but returns a synchronous
+  /// [`blocking::ProjectorControl`].
+  pub fn build_blocking<T: Transport + 'static>(self, port: T) -> blocking::ProjectorControl {
+    self.build(port).into()
+  }
+}
+
+/// State shared by all clones of a [`ProjectorControl`].
+struct Shared {
+  queue: Arc<Queue>,
+  journal: Arc<Journal>,
+  state: Arc<Mutex<ConnectionState>>,
+  profile: Mutex<ModelProfile>,
+
+  /// Resolves once the worker has exited and closed the port
+  done: future::Shared<oneshot::Receiver<()>>,
 }
 
+impl Drop for Shared {
+  fn drop(&mut self) {
+    // let the worker drain the queue, skipping anything nobody is waiting for
+    self.queue.release();
+  }
+}
+
+/// A handle to a projector's command queue.
+///
+/// Handles are cheap to clone and all share the same queue. Once the last one
+/// is dropped, the worker finishes any commands that are still being awaited,
+/// drops the rest and closes the port. Use [`shutdown`](Self::shutdown) to
+/// wait for that to happen.
+#[derive(Clone)]
 pub struct ProjectorControl {
-  cmd_tx: UnboundedSender<SubmittedCommand>,
+  shared: Arc<Shared>,
 }
 
 impl ProjectorControl {
-  pub fn new(port: Box<dyn SerialPort>) -> ProjectorControl {
-    let (cmd_tx, cmd_rx) = unbounded_channel();
-    spawn_command_thread(port, cmd_rx);
+  pub fn new<T: Transport + 'static>(port: T) -> ProjectorControl {
+    ProjectorControl::builder().build(port)
+  }
+
+  pub fn builder() -> ProjectorControlBuilder {
+    ProjectorControlBuilder::default()
+  }
 
-    ProjectorControl { cmd_tx }
+  /// Returns the current state of the serial connection.
+  pub fn connection_state(&self) -> ConnectionState {
+    *self.shared.state.lock().unwrap()
   }
 
   /// Submits a command for future processing.
   ///
   /// The response, if any, will be available by `.await`-ing on the returned
   /// future. The actual command execution takes place on a background thread
-  /// upon which commands are executed in the order they are received (at
-  /// roughly 100ms intervals).
+  /// (or the async [`CommandEngine`]) upon which commands are executed in the
+  /// order they are received (at roughly 100ms intervals).
   ///
   /// Note that this function does have immediate side-effects as the command
   /// will be queued immediately rather than when `.await` is called on the
   /// returned future.
-  pub fn submit_command(&self, command: impl Into<Command>) -> BoxFuture<CommandResult> {
-    let command = command.into();
+  /// The exception is a full queue with [`QueuePolicy::Wait`], in which case
+  /// the command is queued once the future is polled and there is room.
+  pub fn submit_command(&self, command: impl Into<Command>) -> BoxFuture<'static, CommandResult> {
+    self.enqueue(command.into(), false)
+  }
+
+  /// Like [`submit_command`](Self::submit_command), but marks the command as
+  /// background work (e.g. status polling) that may be evicted if the queue is
+  /// full and the policy is [`QueuePolicy::DropOldestBackground`].
+  pub fn submit_background(&self, command: impl Into<Command>) -> BoxFuture<'static, CommandResult> {
+    self.enqueue(command.into(), true)
+  }
+
+  fn enqueue(&self, command: Command, background: bool) -> BoxFuture<'static, CommandResult> {
+    if let Err(e) = command.validate() {
+      return future::ready(Err(e)).boxed();
+    }
+
     let (tx, rx) = oneshot::channel::<CommandResult>();
     let message = SubmittedCommand {
       command: command.clone(),
-      tx
+      tx,
+      background
     };
 
-    match self.cmd_tx.send(message) {
-      // flatten the oneshot's Cancelled case
-      Ok(()) => rx.map(|r| match r {
-        Ok(v) => v,
-        Err(_) => Err(Error::Cancelled {
-          command
-        })
-      }).boxed(),
-
-      Err(_e) => future::ready(Err(Error::CommandSendError {
+    // flatten the oneshot's Cancelled case
+    let response = rx.map(|r| match r {
+      Ok(v) => v,
+      Err(_) => Err(Error::Cancelled {
         command
-      })).boxed()
+      })
+    });
+
+    match self.shared.queue.push(message, None) {
+      Push::Queued => response.boxed(),
+      Push::Rejected(e) => future::ready(Err(e)).boxed(),
+      Push::Full(message) => {
+        let queue = Arc::clone(&self.shared.queue);
+        let mut message = Some(message);
+        let mut slot = SendSlot::default();
+
+        async move {
+          future::poll_fn(|cx| queue.poll_push(cx, &mut message, &mut slot)).await?;
+          response.await
+        }.boxed()
+      }
     }
   }
 
+  /// Returns the queue depth and the command currently executing.
+  pub fn queue_status(&self) -> QueueStatus {
+    self.shared.queue.status()
+  }
+
+  /// Returns recently executed commands with the raw bytes exchanged, oldest
+  /// first.
+  pub fn journal(&self) -> Vec<JournalEntry> {
+    self.shared.journal.entries()
+  }
+
   /// Stop the processing thread.
   ///
   /// This consumes the ProjectorControl instance as it will stop all further
-  /// command processing and close the serial port.
+  /// command processing and close the serial port. Other clones of this handle
+  /// will fail to submit commands from then on.
   pub fn stop(self) -> impl Future<Output = CommandResult> {
-    // annoyingly we basically have to reimplement this function due to lifetime
-    // issues if we call self.submit_command() as it is fn(&self)
-    let (tx, rx) = oneshot::channel::<CommandResult>();
-    let message = SubmittedCommand {
-      command: Command::Stop,
-      tx
-    };
+    self.enqueue(Command::Stop, false)
+  }
+
+  /// Stops processing once all previously queued commands have executed, and
+  /// waits for the worker to exit and close the port.
+  ///
+  /// With [`build_async`](ProjectorControlBuilder::build_async), this only
+  /// completes if the [`CommandEngine`] is being polled.
+  pub fn shutdown(self) -> impl Future<Output = ()> {
+    let done = self.shared.done.clone();
+    let stop = self.stop();
 
-    match self.cmd_tx.send(message) {
-      // flatten the oneshot's Cancelled case
-      Ok(()) => rx.map(|r| match r {
-        Ok(v) => v,
-        Err(_) => Err(Error::Cancelled {
-          command: Command::Stop
-        })
-      }).boxed(),
-
-      Err(_e) => future::ready(Err(Error::CommandSendError {
-        command: Command::Stop
-      })).boxed()
+    async move {
+      // the worker may already be gone, in which case done is resolved too
+      let _ = stop.await;
+      let _ = done.await;
     }
   }
-}
 
-fn read_response(port: &mut Box<dyn SerialPort>, command: &str) -> Result<Option<String>> {
-  let mut response: Vec<u8> = Vec::with_capacity(64);
-  let mut buf: Vec<u8> = vec![0; 32];
+  /// Queries the power state.
+  pub async fn power(&self) -> Result<PowerState> {
+    parse_response("pow", self.submit_command("pow").await)
+  }
 
-  let instant = Instant::now();
-  while instant.elapsed() < RESPONSE_WAIT_PERIOD {
-    match port.read(buf.as_mut_slice()) {
-      Ok(n) => response.extend_from_slice(&buf[..n]),
+  /// Turns the projector on or off.
+  ///
+  /// Note that further commands are held for 30 seconds after powering on and
+  /// 60 seconds after powering off.
+  pub async fn set_power(&self, state: PowerState) -> Result<()> {
+    self.submit_command(("pow", state.to_string())).await.map(|_| ())
+  }
+
+  /// Turns the projector on and waits until it reports that it is on and
+  /// accepts other commands, calling `progress` along the way.
+  ///
+  /// Polling starts once the [`Pacing::power_on`] delay has passed, which
+  /// protects the projector's serial interface while it starts up; projectors
+  /// known to cope can use a shorter delay to return sooner.
+  pub async fn power_on_and_wait(
+    &self,
+    wait: PowerWait,
+    progress: impl FnMut(PowerProgress)
+  ) -> Result<()> {
+    self.power_and_wait(PowerState::On, wait, progress).await
+  }
+
+  /// Turns the projector off and waits until it reports that it is off,
+  /// calling `progress` along the way. See
+  /// [`power_on_and_wait`](Self::power_on_and_wait).
+  pub async fn power_off_and_wait(
+    &self,
+    wait: PowerWait,
+    progress: impl FnMut(PowerProgress)
+  ) -> Result<()> {
+    self.power_and_wait(PowerState::Off, wait, progress).await
+  }
+
+  async fn power_and_wait(
+    &self,
+    target: PowerState,
+    wait: PowerWait,
+    mut progress: impl FnMut(PowerProgress)
+  ) -> Result<()> {
+    let started = Instant::now();
+
+    // some firmware refuses `pow=on` when the projector is already on
+    if self.power().await.ok() != Some(target) {
+      self.set_power(target).await?;
+    }
+    progress(PowerProgress::Sent);
+
+    loop {
+      let power = match self.power().await {
+        Ok(power) => Some(power),
+        Err(e) if e.is_transient() => None,
+        Err(e) => return Err(e)
+      };
+
+      // while warming up, some models report `POW=ON` but refuse everything
+      // else, so make sure a normal query goes through too
+      let ready = match (power, target) {
+        (Some(PowerState::On), PowerState::On) => match self.submit_command("sour").await {
+          Ok(_) => true,
+          Err(e) if e.is_transient() => false,
+          Err(e) => return Err(e)
+        },
+        (Some(power), target) => power == target,
+        (None, _) => false,
+      };
 
-      // keep trying until the time has elapsed
-      Err(ref e) if e.kind() == io::ErrorKind::TimedOut => (),
+      let elapsed = started.elapsed();
+      if ready {
+        progress(PowerProgress::Ready { elapsed });
+        return Ok(());
+      }
 
-      // bubble up all other errors
-      Err(e) => return Err(Error::SerialIOError { source: e })
+      if elapsed + wait.poll_interval > wait.timeout {
+        return Err(Error::Timeout);
+      }
+
+      progress(PowerProgress::Waiting { elapsed, power });
+
+      // sleep in the queue rather than here so nothing else is sent to the
+      // projector in the meantime, and so no async runtime is needed
+      self.submit_command(Command::Sleep(wait.poll_interval)).await?;
     }
   }
 
-  let response = str::from_utf8(&response)?;
-  trace!("full response: {:?}", response);
+  /// Queries the current input source. Fails if the projector is off.
+  pub async fn source(&self) -> Result<Source> {
+    parse_response("sour", self.submit_command("sour").await)
+  }
 
-  // the device seems to echo characters, so expect the first line to be what we
-  // just sent and strip it off
-  if !response.starts_with(command) {
-    return Err(Error::ResponseUnexpectedFormat(response.to_string()));
+  /// Switches to the given input source. Fails if the projector is off.
+  pub async fn set_source(&self, source: &Source) -> Result<()> {
+    self.submit_command(("sour", source.to_string())).await.map(|_| ())
   }
 
-  let response = &response[command.len()..].trim();
-  if response.is_empty() {
-    Ok(None)
-  } else if response.starts_with('*') && response.ends_with('#') {
-    let truncated = &response[1..response.len() - 1];
-    if truncated.to_ascii_lowercase() == "block item" {
-      Err(Error::ResponseBlockItem)
-    } else {
-      Ok(Some(truncated.to_string()))
-    }
-  } else {
-    Err(Error::ResponseUnexpectedFormat(response.to_string()))
+  /// Queries the volume. Fails if the projector is off.
+  pub async fn volume(&self) -> Result<u8> {
+    parse_response("vol", self.submit_command("vol").await)
   }
-}
 
-fn send_get(port: &mut Box<dyn SerialPort>, key: &str) -> CommandResult {
-  port.clear(ClearBuffer::All)?;
-  port.write_all(b"\r")?;
+  /// Sets the volume. Fails if the projector is off.
+  pub async fn set_volume(&self, volume: u8) -> Result<()> {
+    self.submit_command(("vol", volume.to_string())).await.map(|_| ())
+  }
 
-  let mut buf: [u8; 1] = [0; 1];
-  port.read_exact(&mut buf)?;
-  trace!("send_get: prompt buf: {:?}", str::from_utf8(&buf));
+  /// Raises the volume by one step. Fails if the projector is off.
+  pub async fn volume_up(&self) -> Result<()> {
+    self.submit_command(("vol", "+")).await.map(|_| ())
+  }
 
-  if buf[0] as char != '>' {
-    return Err(Error::CommandSendInvalidState);
+  /// Lowers the volume by one step. Fails if the projector is off.
+  pub async fn volume_down(&self) -> Result<()> {
+    self.submit_command(("vol", "-")).await.map(|_| ())
   }
 
-  let command = format!("*{}=?#\r", key);
-  port.write_all(command.as_bytes())?;
-  trace!("send_get: wrote query: {:?}", command);
+  /// Queries whether audio is muted. Fails if the projector is off.
+  pub async fn muted(&self) -> Result<bool> {
+    parse_switch("mute", self.submit_command("mute").await)
+  }
 
-  read_response(port, &command)
-}
+  /// Mutes or unmutes audio. Fails if the projector is off.
+  pub async fn set_muted(&self, muted: bool) -> Result<()> {
+    self.submit_command(("mute", switch(muted))).await.map(|_| ())
+  }
 
-fn send_set(port: &mut Box<dyn SerialPort>, key: &str, value: &str) -> CommandResult {
-  port.clear(ClearBuffer::Input)?;
+  /// Returns the model profile that typed setters are validated against.
+  pub fn profile(&self) -> ModelProfile {
+    self.shared.profile.lock().unwrap().clone()
+  }
 
-  port.write_all(b"\r")?;
+  /// Replaces the model profile for this and every cloned handle.
+  pub fn set_profile(&self, profile: ModelProfile) {
+    *self.shared.profile.lock().unwrap() = profile;
+  }
+
+  /// Queries the model name and switches to its profile (see
+  /// [`ModelProfile::for_model`]), returning the new profile.
+  pub async fn detect_profile(&self) -> Result<ModelProfile> {
+    let profile = ModelProfile::for_model(&self.model().await?);
+    self.set_profile(profile.clone());
 
-  let mut buf: [u8; 1] = [0; 1];
-  port.read_exact(&mut buf)?;
-  trace!("send_set: prompt buf: {:?}", str::from_utf8(&buf));
-  if buf[0] != b'>' {
-    return Err(Error::CommandSendInvalidState);
+    Ok(profile)
   }
 
-  let command = format!("*{}={}#\r", key, value);
-  port.write_all(command.as_bytes())?;
-  trace!("send_set: wrote command: {:?}", command);
+  /// Queries the model name, e.g. `TH685`.
+  pub async fn model(&self) -> Result<String> {
+    parse_response("modelname", self.submit_command("modelname").await)
+  }
 
-  read_response(port, &command)
-}
+  /// Queries the picture mode. Fails if the projector is off.
+  pub async fn picture_mode(&self) -> Result<PictureMode> {
+    parse_response("appmod", self.submit_command("appmod").await)
+  }
 
-fn spawn_command_thread(
-  mut port: Box<dyn SerialPort>,
-  mut rx: UnboundedReceiver<SubmittedCommand>
-) -> JoinHandle<()> {
-  thread::spawn(move || {
-    while let Some(cmd) = rx.blocking_recv() {
-      info!("command: {:?}", &cmd.command);
+  /// Selects a picture mode. Fails if the projector is off.
+  pub async fn set_picture_mode(&self, mode: &PictureMode) -> Result<()> {
+    let profile = self.profile();
+    profile.check("appmod", &profile.picture_modes, mode)?;
+    self.submit_command(("appmod", mode.to_string())).await.map(|_| ())
+  }
+
+  /// Queries a picture level such as brightness. Fails if the projector is
+  /// off.
+  pub async fn picture_level(&self, level: PictureLevel) -> Result<u8> {
+    parse_response(level.key(), self.submit_command(level.key()).await)
+  }
+
+  /// Sets a picture level such as brightness. Fails if the projector is off.
+  ///
+  /// Many models only accept relative adjustments; use
+  /// [`picture_level_up`](Self::picture_level_up) and
+  /// [`picture_level_down`](Self::picture_level_down) with those.
+  pub async fn set_picture_level(&self, level: PictureLevel, value: u8) -> Result<()> {
+    self.profile().check_level(level, value)?;
+    self.submit_command((level.key(), value.to_string())).await.map(|_| ())
+  }
+
+  /// Raises a picture level by one step. Fails if the projector is off.
+  pub async fn picture_level_up(&self, level: PictureLevel) -> Result<()> {
+    self.submit_command((level.key(), "+")).await.map(|_| ())
+  }
+
+  /// Lowers a picture level by one step. Fails if the projector is off.
+  pub async fn picture_level_down(&self, level: PictureLevel) -> Result<()> {
+    self.submit_command((level.key(), "-")).await.map(|_| ())
+  }
 
-      let result = match &cmd.command {
-        Command::Get(key) => send_get(&mut port, key),
-        Command::Set((key, value)) => send_set(&mut port, key, value),
-        Command::Stop => Ok(None),
-        Command::Sleep(d) => {
-          thread::sleep(*d);
-          Ok(None)
+  /// Queries the colour temperature. Fails if the projector is off.
+  pub async fn color_temperature(&self) -> Result<ColorTemperature> {
+    parse_response("ct", self.submit_command("ct").await)
+  }
+
+  /// Sets the colour temperature. Fails if the projector is off.
+  pub async fn set_color_temperature(&self, temperature: &ColorTemperature) -> Result<()> {
+    let profile = self.profile();
+    profile.check("ct", &profile.color_temperatures, temperature)?;
+    self.submit_command(("ct", temperature.to_string())).await.map(|_| ())
+  }
+
+  /// Queries the gamma setting. Fails if the projector is off.
+  pub async fn gamma(&self) -> Result<Gamma> {
+    parse_response("gamma", self.submit_command("gamma").await)
+  }
+
+  /// Sets the gamma. Fails if the projector is off.
+  pub async fn set_gamma(&self, gamma: &Gamma) -> Result<()> {
+    let profile = self.profile();
+    profile.check("gamma", &profile.gammas, gamma)?;
+    self.submit_command(("gamma", gamma.to_string())).await.map(|_| ())
+  }
+
+  /// Queries the aspect ratio mode. Fails if the projector is off.
+  pub async fn aspect_ratio(&self) -> Result<AspectRatio> {
+    parse_response("asp", self.submit_command("asp").await)
+  }
+
+  /// Sets the aspect ratio mode. Fails if the projector is off.
+  pub async fn set_aspect_ratio(&self, aspect: &AspectRatio) -> Result<()> {
+    let profile = self.profile();
+    profile.check("asp", &profile.aspect_ratios, aspect)?;
+    self.submit_command(("asp", aspect.to_string())).await.map(|_| ())
+  }
+
+  /// Queries the hours on the (first) lamp or light source.
+  pub async fn lamp_hours(&self) -> Result<u32> {
+    parse_response("ltim", self.submit_command("ltim").await)
+  }
+
+  /// Queries the lamp mode.
+  pub async fn lamp_mode(&self) -> Result<LampMode> {
+    parse_response("lampm", self.submit_command("lampm").await)
+  }
+
+  /// Sets the lamp mode. Fails if the projector is off.
+  pub async fn set_lamp_mode(&self, mode: &LampMode) -> Result<()> {
+    let profile = self.profile();
+    profile.check("lampm", &profile.lamp_modes, mode)?;
+    self.submit_command(("lampm", mode.to_string())).await.map(|_| ())
+  }
+
+  /// Queries whether the screen is blanked. Fails if the projector is off.
+  pub async fn blanked(&self) -> Result<bool> {
+    parse_switch("blank", self.submit_command("blank").await)
+  }
+
+  /// Blanks or unblanks the screen. Fails if the projector is off.
+  pub async fn set_blank(&self, blank: bool) -> Result<()> {
+    self.submit_command(("blank", switch(blank))).await.map(|_| ())
+  }
+
+  /// Queries whether the picture is frozen. Fails if the projector is off.
+  pub async fn frozen(&self) -> Result<bool> {
+    parse_switch("freeze", self.submit_command("freeze").await)
+  }
+
+  /// Freezes or unfreezes the picture. Fails if the projector is off.
+  pub async fn set_freeze(&self, freeze: bool) -> Result<()> {
+    self.submit_command(("freeze", switch(freeze))).await.map(|_| ())
+  }
+
+  /// Queries the 3D format. Fails if the projector is off.
+  pub async fn three_d_mode(&self) -> Result<ThreeDMode> {
+    parse_response("3d", self.submit_command("3d").await)
+  }
+
+  /// Sets the 3D format. Fails if the projector is off.
+  pub async fn set_three_d_mode(&self, mode: &ThreeDMode) -> Result<()> {
+    let profile = self.profile();
+    profile.check("3d", &profile.three_d_modes, mode)?;
+    self.submit_command(("3d", mode.to_string())).await.map(|_| ())
+  }
+
+  /// Queries how the projector is mounted.
+  pub async fn position(&self) -> Result<ProjectorPosition> {
+    parse_response("pp", self.submit_command("pp").await)
+  }
+
+  /// Sets how the projector is mounted, flipping the picture to suit. Fails
+  /// if the projector is off.
+  pub async fn set_position(&self, position: &ProjectorPosition) -> Result<()> {
+    let profile = self.profile();
+    profile.check("pp", &profile.positions, position)?;
+    self.submit_command(("pp", position.to_string())).await.map(|_| ())
+  }
+
+  /// Queries the keystone correction on `axis`. Fails if the projector is off.
+  pub async fn keystone(&self, axis: KeystoneAxis) -> Result<i8> {
+    parse_response(axis.key(), self.submit_command(axis.key()).await)
+  }
+
+  /// Sets the keystone correction on `axis`. Fails if the projector is off.
+  ///
+  /// As with picture levels, many models only accept relative adjustments
+  /// ([`keystone_up`](Self::keystone_up) and
+  /// [`keystone_down`](Self::keystone_down)).
+  pub async fn set_keystone(&self, axis: KeystoneAxis, value: i8) -> Result<()> {
+    self.profile().check_keystone(axis, value)?;
+    self.submit_command((axis.key(), value.to_string())).await.map(|_| ())
+  }
+
+  /// Increases the keystone correction on `axis` by one step. Fails if the
+  /// projector is off.
+  pub async fn keystone_up(&self, axis: KeystoneAxis) -> Result<()> {
+    self.submit_command((axis.key(), "+")).await.map(|_| ())
+  }
+
+  /// Decreases the keystone correction on `axis` by one step. Fails if the
+  /// projector is off.
+  pub async fn keystone_down(&self, axis: KeystoneAxis) -> Result<()> {
+    self.submit_command((axis.key(), "-")).await.map(|_| ())
+  }
+
+  /// Queries the menu language. Fails if the projector is off.
+  pub async fn language(&self) -> Result<Language> {
+    parse_response("lang", self.submit_command("lang").await)
+  }
+
+  /// Sets the menu language. Fails if the projector is off.
+  pub async fn set_language(&self, language: &Language) -> Result<()> {
+    let profile = self.profile();
+    profile.check("lang", &profile.languages, language)?;
+    self.submit_command(("lang", language.to_string())).await.map(|_| ())
+  }
+
+  /// Presses a button on the remote control.
+  ///
+  /// Presses are paced by [`Pacing::key_press`] so that the on-screen menu can
+  /// keep up.
+  pub async fn press(&self, key: &RemoteKey) -> Result<()> {
+    self.submit_command(key).await.map(|_| ())
+  }
+
+  /// Presses each button in turn, stopping at the first failure.
+  pub async fn press_sequence(&self, keys: &[RemoteKey]) -> Result<()> {
+    for key in keys {
+      self.press(key).await?;
+    }
+
+    Ok(())
+  }
+
+  /// Queries the lamp mode and every lamp and filter counter in the model
+  /// profile.
+  pub async fn maintenance(&self) -> Result<MaintenanceStatus> {
+    let profile = self.profile();
+
+    let mut lamp_hours = Vec::with_capacity(profile.lamp_timers.len());
+    for key in &profile.lamp_timers {
+      lamp_hours.push(parse_response(key, self.submit_command(key.as_str()).await)?);
+    }
+
+    let filter_hours = match &profile.filter_timer {
+      Some(key) => Some(parse_response(key, self.submit_command(key.as_str()).await)?),
+      None => None
+    };
+
+    Ok(MaintenanceStatus {
+      lamp_mode: self.lamp_mode().await?,
+      lamp_hours,
+      filter_hours,
+    })
+  }
+
+  /// Runs each step of `sequence` in order, stopping at the first failure
+  /// unless the sequence says to continue.
+  pub async fn run_sequence(&self, sequence: &Sequence) -> SequenceReport {
+    let mut report = SequenceReport::default();
+
+    // nested `when` steps are walked with an explicit stack
+    let mut stack = vec![sequence.steps.iter()];
+    while let Some(steps) = stack.last_mut() {
+      let step = match steps.next() {
+        Some(step) => step,
+        None => {
+          stack.pop();
+          continue;
         }
       };
 
-      debug!("command {:?} result: {:?}", &cmd.command, &result);
+      let depth = stack.len() - 1;
+      let outcome = match step {
+        // stopping the worker would break every later command, and sleeps
+        // have their own step
+        Step::Command(command @ (Command::Stop | Command::Sleep(_))) => {
+          Err(Error::InvalidCommand(command.to_string()))
+        },
+        Step::Command(command) => self.submit_command(command.clone()).await,
+        Step::Wait(duration) => self.submit_command(Command::Sleep(*duration)).await,
+        Step::Power(state) => {
+          let wait = PowerWait::default();
+          let result = match state {
+            PowerState::On => self.power_on_and_wait(wait, |_| ()).await,
+            PowerState::Off => self.power_off_and_wait(wait, |_| ()).await,
+          };
 
-      if let Err(e) = cmd.tx.send(result) {
-        // we can't do much if this fails, but dropping it normally after this
-        // iteration will at least raise Cancelled on the other end (though the
-        // other end probably no longer exists)
-        debug!("command ({:?}) response send failed: {:?}", &cmd.command, e);
-      }
+          result.map(|_| None)
+        },
+        Step::When { key, equals, steps } => {
+          let response = self.submit_command(key.as_str()).await;
+          match response.and_then(|r| response_value(key, &r).map(str::to_string)) {
+            Ok(value) if value.eq_ignore_ascii_case(equals) => {
+              stack.push(steps.iter());
+              Ok(Some(value))
+            },
+            Ok(_) => {
+              report.steps.push(StepReport {
+                step: step.to_string(),
+                depth,
+                outcome: StepOutcome::Skipped
+              });
+              continue;
+            },
+            Err(e) => Err(e)
+          }
+        },
+      };
 
-      if let Command::Stop = &cmd.command {
+      let failed = outcome.is_err();
+      report.steps.push(StepReport {
+        step: step.to_string(),
+        depth,
+        outcome: match outcome {
+          Ok(response) => StepOutcome::Done(response),
+          Err(e) => StepOutcome::Failed(e),
+        }
+      });
+
+      if failed && sequence.on_error == OnError::Stop {
+        report.stopped = true;
         break;
       }
+    }
 
-      // wait a bit between commands for safety
-      let delay_millis = match cmd.command {
-        Command::Set((k, v)) => {
-          if k.to_ascii_lowercase() == "pow" {
-            if v.to_ascii_lowercase() == "off" {
-              // power off takes longer
-              60_000
-            } else {
-              30_000
-            }
-          } else {
-            500
+    report
+  }
+
+  /// Reads every setting in [`ModelProfile::settings`] into a [`Snapshot`].
+  /// Settings the projector refuses to report (with `Block item`) are left
+  /// out; any other error aborts the snapshot. Fails if the projector is off.
+  pub async fn snapshot(&self) -> Result<Snapshot> {
+    let profile = self.profile();
+    let mut snapshot = Snapshot {
+      model: profile.model.clone(),
+      ..Snapshot::default()
+    };
+
+    for key in profile.settings() {
+      let response = match self.submit_command(key).await {
+        Ok(response) => response,
+        Err(Error::ResponseBlockItem) => {
+          debug!("skipping setting {}: refused by projector", key);
+          continue;
+        },
+        Err(e) => return Err(e)
+      };
+
+      let value = response_value(key, &response)?;
+      snapshot.settings.insert(key.to_string(), value.to_ascii_lowercase());
+    }
+
+    Ok(snapshot)
+  }
+
+  /// Applies every setting in `snapshot`, continuing past failures. Values
+  /// the current profile doesn't support are reported as failed without being
+  /// sent. Commands are paced like any other, so a full restore takes several
+  /// seconds.
+  pub async fn restore(&self, snapshot: &Snapshot) -> RestoreReport {
+    let mut report = RestoreReport::default();
+    let profile = self.profile();
+
+    for command in snapshot.commands(&profile) {
+      let key = match &command {
+        Command::Set((key, value)) => match profile.check_setting(key, value) {
+          Ok(()) => key.clone(),
+          Err(e) => {
+            report.failed.push((key.clone(), e));
+            continue;
           }
         },
-        _ => 1
+        _ => continue
       };
 
-      // hack: sending commands too quickly after powering on crashes the serial
-      // interface, so block the processing thread for a bit
-      // note that this does nothing to protect us if we accidentally send commands
-      // after the user presses buttons on the projector - we'll need to rely on
-      trace!("waiting {}ms after command", delay_millis);
-      thread::sleep(Duration::from_millis(delay_millis));
+      match self.submit_command(command).await {
+        Ok(_) => report.applied.push(key),
+        Err(e) => report.failed.push((key, e)),
+      }
     }
-  })
+
+    report
+  }
 }
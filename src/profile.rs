@@ -0,0 +1,270 @@
+//! What individual projector models support.
+//!
+//! The serial protocol is mostly shared across BenQ's range, but which values
+//! each key accepts differs from model to model. Sending an unsupported value
+//! usually just gets a `Block item` response; checking against a
+//! [`ModelProfile`] first gives a more useful error and lets tools offer only
+//! the choices that will work.
+
+use std::fmt;
+use std::ops::RangeInclusive;
+
+use crate::{Error, Result};
+use crate::values::{
+  AspectRatio, ColorTemperature, Gamma, KeystoneAxis, LampMode, Language, PictureLevel, PictureMode,
+  ProjectorPosition, ThreeDMode
+};
+
+/// The values a projector model accepts.
+///
+/// Lists set to `None` are unrestricted: any value, including ones this crate
+/// doesn't know about, is sent to the projector as is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModelProfile {
+  /// The model name, as reported by `modelname=?`
+  pub model: String,
+
+  pub picture_modes: Option<Vec<PictureMode>>,
+  pub color_temperatures: Option<Vec<ColorTemperature>>,
+  pub gammas: Option<Vec<Gamma>>,
+  pub aspect_ratios: Option<Vec<AspectRatio>>,
+
+  pub brightness: RangeInclusive<u8>,
+  pub contrast: RangeInclusive<u8>,
+  pub color: RangeInclusive<u8>,
+  pub sharpness: RangeInclusive<u8>,
+
+  pub lamp_modes: Option<Vec<LampMode>>,
+
+  /// Keys of the lamp hour counters, e.g. `ltim` and `ltim2` on dual-lamp
+  /// models
+  pub lamp_timers: Vec<String>,
+
+  /// Key of the filter hour counter, on models with a dust filter
+  pub filter_timer: Option<String>,
+
+  /// Supported 3D formats; an empty list means the model has no 3D support
+  pub three_d_modes: Option<Vec<ThreeDMode>>,
+
+  pub positions: Option<Vec<ProjectorPosition>>,
+  pub languages: Option<Vec<Language>>,
+
+  /// Keystone correction ranges, or `None` if the model can't correct on that
+  /// axis
+  pub vertical_keystone: Option<RangeInclusive<i8>>,
+  pub horizontal_keystone: Option<RangeInclusive<i8>>,
+}
+
+impl Default for ModelProfile {
+  fn default() -> Self {
+    ModelProfile::generic("generic")
+  }
+}
+
+impl ModelProfile {
+  /// A permissive profile for models without a profile of their own.
+  pub fn generic(model: &str) -> ModelProfile {
+    ModelProfile {
+      model: model.to_string(),
+      picture_modes: None,
+      color_temperatures: None,
+      gammas: None,
+      aspect_ratios: None,
+      brightness: 0..=100,
+      contrast: 0..=100,
+      color: 0..=100,
+      sharpness: 0..=31,
+      lamp_modes: None,
+      lamp_timers: vec!["ltim".to_string()],
+      filter_timer: None,
+      three_d_modes: None,
+      positions: None,
+      languages: None,
+      vertical_keystone: Some(i8::MIN..=i8::MAX),
+      horizontal_keystone: Some(i8::MIN..=i8::MAX),
+    }
+  }
+
+  /// The BenQ TH685, per its user manual.
+  pub fn th685() -> ModelProfile {
+    ModelProfile {
+      model: "TH685".to_string(),
+      picture_modes: Some(vec![
+        PictureMode::Bright,
+        PictureMode::Vivid,
+        PictureMode::Cinema,
+        PictureMode::Game,
+        PictureMode::Sport,
+        PictureMode::User1,
+        PictureMode::ThreeD,
+      ]),
+      color_temperatures: Some(vec![
+        ColorTemperature::Warm,
+        ColorTemperature::Normal,
+        ColorTemperature::Cool,
+        ColorTemperature::Native,
+      ]),
+      gammas: Some(vec![
+        Gamma::G16,
+        Gamma::G18,
+        Gamma::G20,
+        Gamma::G22,
+        Gamma::G24,
+        Gamma::G25,
+        Gamma::G26,
+        Gamma::G28,
+      ]),
+      aspect_ratios: Some(vec![
+        AspectRatio::Auto,
+        AspectRatio::R4x3,
+        AspectRatio::R16x9,
+        AspectRatio::R16x10,
+      ]),
+      brightness: 0..=100,
+      contrast: 0..=100,
+      color: 0..=100,
+      sharpness: 0..=15,
+      lamp_modes: Some(vec![LampMode::Normal, LampMode::Eco, LampMode::SmartEco]),
+      lamp_timers: vec!["ltim".to_string()],
+      filter_timer: None,
+      three_d_modes: Some(vec![
+        ThreeDMode::Off,
+        ThreeDMode::Auto,
+        ThreeDMode::FrameSequential,
+        ThreeDMode::TopBottom,
+        ThreeDMode::SideBySide,
+        ThreeDMode::FramePacking,
+      ]),
+      positions: Some(ProjectorPosition::KNOWN.to_vec()),
+      languages: None,
+      vertical_keystone: Some(-30..=30),
+      horizontal_keystone: None,
+    }
+  }
+
+  /// Returns the profile for the model named `model` (case-insensitively),
+  /// falling back to [`generic`](Self::generic).
+  pub fn for_model(model: &str) -> ModelProfile {
+    match model.trim().to_ascii_uppercase().as_str() {
+      "TH685" | "TH685I" => ModelProfile { model: model.trim().to_string(), ..ModelProfile::th685() },
+      _ => ModelProfile::generic(model.trim())
+    }
+  }
+
+  /// Returns the keys of the persistent settings this model can read back,
+  /// in the order they should be restored. The picture mode comes first as
+  /// the other picture settings are stored per mode.
+  pub fn settings(&self) -> Vec<&'static str> {
+    // an empty list means the model lacks the feature entirely
+    fn supported<T>(allowed: &Option<Vec<T>>) -> bool {
+      !matches!(allowed, Some(a) if a.is_empty())
+    }
+
+    let mut keys = vec!["appmod"];
+    keys.extend(PictureLevel::ALL.iter().map(|level| level.key()));
+    keys.extend(&["ct", "gamma", "asp"]);
+
+    if supported(&self.lamp_modes) {
+      keys.push("lampm");
+    }
+    if supported(&self.three_d_modes) {
+      keys.push("3d");
+    }
+
+    keys.push("pp");
+    for axis in [KeystoneAxis::Vertical, KeystoneAxis::Horizontal].iter() {
+      if self.keystone(*axis).is_some() {
+        keys.push(axis.key());
+      }
+    }
+
+    keys.extend(&["lang", "vol"]);
+    keys
+  }
+
+  /// Returns the accepted range of a picture level.
+  pub fn range(&self, level: PictureLevel) -> RangeInclusive<u8> {
+    match level {
+      PictureLevel::Brightness => self.brightness.clone(),
+      PictureLevel::Contrast => self.contrast.clone(),
+      PictureLevel::Color => self.color.clone(),
+      PictureLevel::Sharpness => self.sharpness.clone(),
+    }
+  }
+
+  /// Checks that `value` is in the range for `level`.
+  pub fn check_level(&self, level: PictureLevel, value: u8) -> Result<()> {
+    if self.range(level).contains(&value) {
+      Ok(())
+    } else {
+      Err(self.unsupported(level.key(), value))
+    }
+  }
+
+  /// Returns the keystone range on `axis`, or `None` if it can't be
+  /// corrected.
+  pub fn keystone(&self, axis: KeystoneAxis) -> Option<RangeInclusive<i8>> {
+    match axis {
+      KeystoneAxis::Vertical => self.vertical_keystone.clone(),
+      KeystoneAxis::Horizontal => self.horizontal_keystone.clone(),
+    }
+  }
+
+  /// Checks that keystone on `axis` can be set to `value`.
+  pub fn check_keystone(&self, axis: KeystoneAxis, value: i8) -> Result<()> {
+    match self.keystone(axis) {
+      Some(range) if range.contains(&value) => Ok(()),
+      _ => Err(self.unsupported(axis.key(), value))
+    }
+  }
+
+  /// Checks that `value` is one of the `allowed` values of `key`, if they are
+  /// restricted.
+  pub fn check<T>(&self, key: &str, allowed: &Option<Vec<T>>, value: &T) -> Result<()>
+  where
+    T: PartialEq + fmt::Display
+  {
+    match allowed {
+      Some(allowed) if !allowed.contains(value) => Err(self.unsupported(key, value)),
+      _ => Ok(())
+    }
+  }
+
+  /// Checks a raw setting, as stored in a [`Snapshot`](crate::Snapshot),
+  /// with whichever of the checks above applies to `key`. Keys without a
+  /// check, such as `vol`, are accepted as is.
+  pub fn check_setting(&self, key: &str, value: &str) -> Result<()> {
+    let invalid = || Error::InvalidValue(value.to_string());
+
+    match key {
+      "appmod" => self.check(key, &self.picture_modes, &value.parse()?),
+      "ct" => self.check(key, &self.color_temperatures, &value.parse()?),
+      "gamma" => self.check(key, &self.gammas, &value.parse()?),
+      "asp" => self.check(key, &self.aspect_ratios, &value.parse()?),
+      "lampm" => self.check(key, &self.lamp_modes, &value.parse()?),
+      "3d" => self.check(key, &self.three_d_modes, &value.parse()?),
+      "pp" => self.check(key, &self.positions, &value.parse()?),
+      "lang" => self.check(key, &self.languages, &value.parse()?),
+      _ => {
+        if let Some(level) = PictureLevel::ALL.iter().find(|level| level.key() == key) {
+          return self.check_level(*level, value.parse().map_err(|_| invalid())?);
+        }
+
+        let axes = [KeystoneAxis::Vertical, KeystoneAxis::Horizontal];
+        if let Some(axis) = axes.iter().find(|axis| axis.key() == key) {
+          return self.check_keystone(*axis, value.parse().map_err(|_| invalid())?);
+        }
+
+        Ok(())
+      }
+    }
+  }
+
+  fn unsupported(&self, key: &str, value: impl fmt::Display) -> Error {
+    Error::Unsupported {
+      model: self.model.clone(),
+      key: key.to_string(),
+      value: value.to_string(),
+    }
+  }
+}
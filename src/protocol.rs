@@ -0,0 +1,592 @@
+//! Framing for BenQ's serial protocol.
+//!
+//! Every exchange with the projector looks roughly like this:
+//!
+//! ```text
+//! -> \r
+//! <- >
+//! -> *pow=?#\r
+//! <- *pow=?#           (echo)
+//! <- *POW=ON#          (response frame)
+//! ```
+//!
+//! The line is not particularly clean in practice: bytes left over from a
+//! previous (timed out) command or plain line noise may appear anywhere. The
+//! functions here scan for the prompt and for `*...#` frame boundaries rather
+//! than expecting them at fixed offsets, so a bit of garbage costs at most one
+//! command rather than every command until the buffers happen to clear.
+//!
+//! Not every firmware generation behaves quite like this: some don't echo
+//! input, some expect `\r\n` line endings and some never print a prompt.
+//! [`ProtocolOptions`] covers these variations.
+
+use std::io::{self, Read, Write};
+use std::str::{self, FromStr};
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "async-serial")]
+use futures::FutureExt;
+use futures::future::BoxFuture;
+use log::trace;
+use serialport::{ClearBuffer, SerialPort};
+
+use crate::{check_key, check_value, rt, CommandResult, Error, Result};
+
+/// The maximum time to wait for the prompt or a response frame.
+pub const RESPONSE_WAIT_PERIOD: Duration = Duration::from_millis(200);
+
+/// Whether the projector echoes commands back before responding.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EchoMode {
+  /// Strip the echo if it is received, otherwise treat the first frame as the
+  /// response. Note that this waits out the full response period on projectors
+  /// that don't echo.
+  #[default]
+  Auto,
+
+  /// The projector always echoes; a missing echo is an error.
+  Always,
+
+  /// The projector never echoes; every received frame is a response.
+  Never,
+}
+
+impl FromStr for EchoMode {
+  type Err = Error;
+
+  fn from_str(s: &str) -> Result<Self> {
+    match s.to_ascii_lowercase().as_str() {
+      "auto" => Ok(EchoMode::Auto),
+      "always" => Ok(EchoMode::Always),
+      "never" => Ok(EchoMode::Never),
+      _ => Err(Error::InvalidProtocolOption(s.to_string()))
+    }
+  }
+}
+
+/// The line ending used to terminate commands.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LineEnding {
+  /// `\r`, used by most BenQ projectors
+  #[default]
+  Cr,
+
+  /// `\r\n`
+  CrLf,
+}
+
+impl LineEnding {
+  pub fn as_bytes(&self) -> &'static [u8] {
+    match self {
+      LineEnding::Cr => b"\r",
+      LineEnding::CrLf => b"\r\n",
+    }
+  }
+}
+
+impl FromStr for LineEnding {
+  type Err = Error;
+
+  fn from_str(s: &str) -> Result<Self> {
+    match s.to_ascii_lowercase().as_str() {
+      "cr" => Ok(LineEnding::Cr),
+      "crlf" => Ok(LineEnding::CrLf),
+      _ => Err(Error::InvalidProtocolOption(s.to_string()))
+    }
+  }
+}
+
+/// Whether the projector must print its `>` prompt before accepting a command.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PromptMode {
+  /// A missing prompt is an error ([`Error::CommandSendInvalidState`]).
+  #[default]
+  Required,
+
+  /// Wait briefly for the prompt but send the command regardless.
+  Optional,
+}
+
+impl FromStr for PromptMode {
+  type Err = Error;
+
+  fn from_str(s: &str) -> Result<Self> {
+    match s.to_ascii_lowercase().as_str() {
+      "required" => Ok(PromptMode::Required),
+      "optional" => Ok(PromptMode::Optional),
+      _ => Err(Error::InvalidProtocolOption(s.to_string()))
+    }
+  }
+}
+
+/// Options covering differences between firmware generations.
+///
+/// The defaults match the behavior of e.g. the TH685: commands are echoed
+/// (though this is detected automatically), lines end in `\r` and a prompt is
+/// always printed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ProtocolOptions {
+  pub echo: EchoMode,
+  pub line_ending: LineEnding,
+  pub prompt: PromptMode,
+}
+
+/// A byte stream connected to a projector.
+///
+/// This is implemented for serial ports but may be implemented for anything
+/// else that speaks the protocol, e.g. network bridges or fakes for testing.
+pub trait Transport: Read + Write + Send {
+  /// Discards any buffered (unread or unwritten) data.
+  fn clear(&mut self, buffer: ClearBuffer) -> Result<()>;
+}
+
+impl Transport for Box<dyn SerialPort> {
+  fn clear(&mut self, buffer: ClearBuffer) -> Result<()> {
+    Ok(SerialPort::clear(self.as_ref(), buffer)?)
+  }
+}
+
+impl Transport for Box<dyn Transport> {
+  fn clear(&mut self, buffer: ClearBuffer) -> Result<()> {
+    self.as_mut().clear(buffer)
+  }
+}
+
+/// An asynchronous byte stream connected to a projector.
+///
+/// This is the counterpart to [`Transport`] used by the async command engine.
+/// With the `async-serial` feature it is implemented for
+/// `tokio_serial::SerialStream`.
+pub trait AsyncTransport: Send {
+  /// Reads available data into `buf`, returning the number of bytes read. A
+  /// return value of 0 indicates the stream has closed.
+  fn read<'a>(&'a mut self, buf: &'a mut [u8]) -> BoxFuture<'a, io::Result<usize>>;
+
+  /// Writes all of `buf`.
+  fn write_all<'a>(&'a mut self, buf: &'a [u8]) -> BoxFuture<'a, io::Result<()>>;
+
+  /// Discards any buffered (unread or unwritten) data.
+  fn clear(&mut self, buffer: ClearBuffer) -> Result<()>;
+}
+
+impl<T: AsyncTransport + ?Sized> AsyncTransport for Box<T> {
+  fn read<'a>(&'a mut self, buf: &'a mut [u8]) -> BoxFuture<'a, io::Result<usize>> {
+    self.as_mut().read(buf)
+  }
+
+  fn write_all<'a>(&'a mut self, buf: &'a [u8]) -> BoxFuture<'a, io::Result<()>> {
+    self.as_mut().write_all(buf)
+  }
+
+  fn clear(&mut self, buffer: ClearBuffer) -> Result<()> {
+    self.as_mut().clear(buffer)
+  }
+}
+
+#[cfg(feature = "async-serial")]
+impl AsyncTransport for tokio_serial::SerialStream {
+  fn read<'a>(&'a mut self, buf: &'a mut [u8]) -> BoxFuture<'a, io::Result<usize>> {
+    tokio::io::AsyncReadExt::read(self, buf).boxed()
+  }
+
+  fn write_all<'a>(&'a mut self, buf: &'a [u8]) -> BoxFuture<'a, io::Result<()>> {
+    tokio::io::AsyncWriteExt::write_all(self, buf).boxed()
+  }
+
+  fn clear(&mut self, buffer: ClearBuffer) -> Result<()> {
+    Ok(SerialPort::clear(self, buffer)?)
+  }
+}
+
+/// A `*...#` frame found in a buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Frame<'a> {
+  /// Offset of the leading `*`
+  pub start: usize,
+
+  /// Offset just past the trailing `#`
+  pub end: usize,
+
+  /// The frame contents, excluding the delimiters
+  pub body: &'a [u8],
+}
+
+/// An iterator over the complete frames in a buffer.
+///
+/// Incomplete frames are skipped: a `*` restarts the current frame and a line
+/// break abandons it, so a truncated frame followed by a complete one yields
+/// only the complete one.
+pub struct Frames<'a> {
+  buf: &'a [u8],
+  pos: usize,
+}
+
+impl<'a> Iterator for Frames<'a> {
+  type Item = Frame<'a>;
+
+  fn next(&mut self) -> Option<Frame<'a>> {
+    let mut start = None;
+
+    while self.pos < self.buf.len() {
+      let b = self.buf[self.pos];
+      self.pos += 1;
+
+      match (b, start) {
+        (b'*', _) => start = Some(self.pos - 1),
+        (b'#', Some(s)) => return Some(Frame {
+          start: s,
+          end: self.pos,
+          body: &self.buf[s + 1..self.pos - 1]
+        }),
+        (b'\r', _) | (b'\n', _) => start = None,
+        _ => ()
+      }
+    }
+
+    None
+  }
+}
+
+/// Returns an iterator over all complete `*...#` frames in `buf`.
+pub fn frames(buf: &[u8]) -> Frames<'_> {
+  Frames { buf, pos: 0 }
+}
+
+/// Returns the offset just past the first prompt character in `buf`, if any.
+pub fn find_prompt(buf: &[u8]) -> Option<usize> {
+  buf.iter().position(|&b| b == b'>').map(|i| i + 1)
+}
+
+fn find_subslice(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+  if needle.is_empty() {
+    return Some(0);
+  }
+
+  haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+/// Encodes a command body (e.g. `pow=?`) as a frame, without line ending.
+pub fn encode_frame(body: &str) -> Vec<u8> {
+  format!("*{}#", body).into_bytes()
+}
+
+/// Returns the part of `buf` following the echo of `echo`, if the echo can be
+/// found.
+fn after_echo<'a>(buf: &'a [u8], echo: &[u8]) -> Option<&'a [u8]> {
+  find_subslice(buf, echo).map(|i| &buf[i + echo.len()..])
+}
+
+/// Attempts to extract the response to the command `echo` (a complete frame,
+/// without the line ending) from `buf`.
+///
+/// Unless echo is disabled, anything preceding the echo is discarded. In
+/// [`EchoMode::Auto`], if the echo is missing, garbled or truncated, the first
+/// complete frame that isn't itself the echo is taken as the response.
+///
+/// Returns `None` if no response frame has been received (yet).
+pub fn parse_response(buf: &[u8], echo: &[u8], mode: EchoMode) -> Option<CommandResult> {
+  let search = match mode {
+    EchoMode::Auto => after_echo(buf, echo).unwrap_or(buf),
+    EchoMode::Always => after_echo(buf, echo)?,
+    EchoMode::Never => buf,
+  };
+
+  let frame = frames(search)
+    .find(|f| mode == EchoMode::Never || &search[f.start..f.end] != echo)?;
+  let body = match str::from_utf8(frame.body) {
+    Ok(body) => body,
+    Err(e) => return Some(Err(e.into()))
+  };
+
+  if body.eq_ignore_ascii_case("block item") {
+    Some(Err(Error::ResponseBlockItem))
+  } else {
+    Some(Ok(Some(body.to_string())))
+  }
+}
+
+/// Interprets a response buffer once no further data is expected.
+///
+/// This is [`parse_response`], except that a buffer without a response frame
+/// is either an empty response (if nothing but whitespace follows the echo, or
+/// the whole buffer is empty when no echo is expected) or an error.
+pub fn finish_response(buf: &[u8], echo: &[u8], mode: EchoMode) -> CommandResult {
+  if let Some(result) = parse_response(buf, echo, mode) {
+    return result;
+  }
+
+  let rest = match (mode, after_echo(buf, echo)) {
+    (EchoMode::Never, _) => buf,
+    (_, Some(rest)) => rest,
+    (EchoMode::Auto, None) => buf,
+    (EchoMode::Always, None) => return Err(Error::ResponseUnexpectedFormat(
+      String::from_utf8_lossy(buf).to_string()
+    ))
+  };
+
+  let rest = String::from_utf8_lossy(rest);
+  let rest = rest.trim_matches(|c: char| c.is_ascii_whitespace() || c == '>');
+  if rest.is_empty() {
+    Ok(None)
+  } else {
+    Err(Error::ResponseUnexpectedFormat(rest.to_string()))
+  }
+}
+
+/// Reads from `port` into `response` until `done` returns true or `period` has
+/// elapsed.
+fn read_until<T>(
+  port: &mut T,
+  response: &mut Vec<u8>,
+  period: Duration,
+  mut done: impl FnMut(&[u8]) -> bool
+) -> Result<bool>
+where
+  T: Transport + ?Sized
+{
+  let mut buf: Vec<u8> = vec![0; 32];
+
+  let instant = Instant::now();
+  while instant.elapsed() < period {
+    match port.read(buf.as_mut_slice()) {
+      Ok(n) => {
+        response.extend_from_slice(&buf[..n]);
+        if done(response) {
+          return Ok(true);
+        }
+      },
+
+      // keep trying until the time has elapsed
+      Err(ref e) if e.kind() == io::ErrorKind::TimedOut => (),
+
+      // bubble up all other errors
+      Err(e) => return Err(Error::SerialIOError { source: e })
+    }
+  }
+
+  Ok(false)
+}
+
+/// Wakes the projector's command interface and waits for its prompt,
+/// discarding anything received before it.
+pub fn wait_for_prompt<T>(port: &mut T, options: &ProtocolOptions) -> Result<bool>
+where
+  T: Transport + ?Sized
+{
+  port.write_all(options.line_ending.as_bytes())?;
+
+  let mut buf = Vec::with_capacity(8);
+  let found = read_until(port, &mut buf, RESPONSE_WAIT_PERIOD, |b| {
+    find_prompt(b).is_some()
+  })?;
+
+  trace!("wait_for_prompt: prompt buf: {:?}", String::from_utf8_lossy(&buf));
+  Ok(found)
+}
+
+/// Reads the projector's response to `frame`, a command frame as encoded by
+/// [`encode_frame`].
+pub fn read_response<T>(port: &mut T, frame: &[u8], options: &ProtocolOptions) -> CommandResult
+where
+  T: Transport + ?Sized
+{
+  // stop early once the echo (if expected) and a response frame have been
+  // seen; in auto mode, frames received before the echo are stale and only
+  // used as a last resort
+  let mut response: Vec<u8> = Vec::with_capacity(64);
+  read_until(port, &mut response, RESPONSE_WAIT_PERIOD, |b| {
+    let echo_seen = options.echo == EchoMode::Never || after_echo(b, frame).is_some();
+    echo_seen && parse_response(b, frame, options.echo).is_some()
+  })?;
+
+  trace!("full response: {:?}", String::from_utf8_lossy(&response));
+  finish_response(&response, frame, options.echo)
+}
+
+fn exchange<T>(
+  port: &mut T,
+  clear: ClearBuffer,
+  body: &str,
+  options: &ProtocolOptions
+) -> CommandResult
+where
+  T: Transport + ?Sized
+{
+  port.clear(clear)?;
+
+  if !wait_for_prompt(port, options)? && options.prompt == PromptMode::Required {
+    return Err(Error::CommandSendInvalidState);
+  }
+
+  let frame = encode_frame(body);
+  let mut command = frame.clone();
+  command.extend_from_slice(options.line_ending.as_bytes());
+  port.write_all(&command)?;
+  trace!("exchange: wrote command: {:?}", String::from_utf8_lossy(&command));
+
+  read_response(port, &frame, options)
+}
+
+/// Queries `key`, returning the projector's response.
+///
+/// Fails with [`Error::InvalidCommand`] without sending anything if `key` is
+/// not a [valid](crate::Command::validate) key.
+pub fn send_get<T>(port: &mut T, key: &str, options: &ProtocolOptions) -> CommandResult
+where
+  T: Transport + ?Sized
+{
+  check_key(key)?;
+  exchange(port, ClearBuffer::All, &format!("{}=?", key), options)
+}
+
+/// Sets `key` to `value`, returning the projector's response.
+pub fn send_set<T>(port: &mut T, key: &str, value: &str, options: &ProtocolOptions) -> CommandResult
+where
+  T: Transport + ?Sized
+{
+  check_key(key)?;
+  check_value(value)?;
+  exchange(port, ClearBuffer::Input, &format!("{}={}", key, value), options)
+}
+
+/// Sends the bare action `key`, e.g. `*up#` for a remote key press, returning
+/// the projector's response if any.
+pub fn send_action<T>(port: &mut T, key: &str, options: &ProtocolOptions) -> CommandResult
+where
+  T: Transport + ?Sized
+{
+  check_key(key)?;
+  exchange(port, ClearBuffer::Input, key, options)
+}
+
+/// Reads from `port` into `response` until `done` returns true or `period` has
+/// elapsed, without blocking the executor.
+async fn read_until_async<T>(
+  port: &mut T,
+  response: &mut Vec<u8>,
+  period: Duration,
+  mut done: impl FnMut(&[u8]) -> bool
+) -> Result<bool>
+where
+  T: AsyncTransport + ?Sized
+{
+  let mut buf: Vec<u8> = vec![0; 32];
+
+  let deadline = Instant::now() + period;
+  loop {
+    let remaining = deadline.saturating_duration_since(Instant::now());
+    if remaining == Duration::from_secs(0) {
+      return Ok(false);
+    }
+
+    match rt::timeout(remaining, port.read(buf.as_mut_slice())).await {
+      None => return Ok(false),
+
+      Some(Ok(0)) => return Err(Error::SerialIOError {
+        source: io::ErrorKind::UnexpectedEof.into()
+      }),
+
+      Some(Ok(n)) => {
+        response.extend_from_slice(&buf[..n]);
+        if done(response) {
+          return Ok(true);
+        }
+      },
+
+      Some(Err(ref e)) if e.kind() == io::ErrorKind::TimedOut => (),
+      Some(Err(e)) => return Err(Error::SerialIOError { source: e })
+    }
+  }
+}
+
+/// Async version of [`wait_for_prompt`].
+pub async fn wait_for_prompt_async<T>(port: &mut T, options: &ProtocolOptions) -> Result<bool>
+where
+  T: AsyncTransport + ?Sized
+{
+  port.write_all(options.line_ending.as_bytes()).await?;
+
+  let mut buf = Vec::with_capacity(8);
+  let found = read_until_async(port, &mut buf, RESPONSE_WAIT_PERIOD, |b| {
+    find_prompt(b).is_some()
+  }).await?;
+
+  trace!("wait_for_prompt_async: prompt buf: {:?}", String::from_utf8_lossy(&buf));
+  Ok(found)
+}
+
+/// Async version of [`read_response`].
+pub async fn read_response_async<T>(
+  port: &mut T,
+  frame: &[u8],
+  options: &ProtocolOptions
+) -> CommandResult
+where
+  T: AsyncTransport + ?Sized
+{
+  let mut response: Vec<u8> = Vec::with_capacity(64);
+  read_until_async(port, &mut response, RESPONSE_WAIT_PERIOD, |b| {
+    let echo_seen = options.echo == EchoMode::Never || after_echo(b, frame).is_some();
+    echo_seen && parse_response(b, frame, options.echo).is_some()
+  }).await?;
+
+  trace!("full response: {:?}", String::from_utf8_lossy(&response));
+  finish_response(&response, frame, options.echo)
+}
+
+async fn exchange_async<T>(
+  port: &mut T,
+  clear: ClearBuffer,
+  body: &str,
+  options: &ProtocolOptions
+) -> CommandResult
+where
+  T: AsyncTransport + ?Sized
+{
+  port.clear(clear)?;
+
+  if !wait_for_prompt_async(port, options).await? && options.prompt == PromptMode::Required {
+    return Err(Error::CommandSendInvalidState);
+  }
+
+  let frame = encode_frame(body);
+  let mut command = frame.clone();
+  command.extend_from_slice(options.line_ending.as_bytes());
+  port.write_all(&command).await?;
+  trace!("exchange_async: wrote command: {:?}", String::from_utf8_lossy(&command));
+
+  read_response_async(port, &frame, options).await
+}
+
+/// Async version of [`send_get`].
+pub async fn send_get_async<T>(port: &mut T, key: &str, options: &ProtocolOptions) -> CommandResult
+where
+  T: AsyncTransport + ?Sized
+{
+  check_key(key)?;
+  exchange_async(port, ClearBuffer::All, &format!("{}=?", key), options).await
+}
+
+/// Async version of [`send_set`].
+pub async fn send_set_async<T>(
+  port: &mut T,
+  key: &str,
+  value: &str,
+  options: &ProtocolOptions
+) -> CommandResult
+where
+  T: AsyncTransport + ?Sized
+{
+  check_key(key)?;
+  check_value(value)?;
+  exchange_async(port, ClearBuffer::Input, &format!("{}={}", key, value), options).await
+}
+
+/// Async version of [`send_action`].
+pub async fn send_action_async<T>(port: &mut T, key: &str, options: &ProtocolOptions) -> CommandResult
+where
+  T: AsyncTransport + ?Sized
+{
+  check_key(key)?;
+  exchange_async(port, ClearBuffer::Input, key, options).await
+}
@@ -0,0 +1,375 @@
+//! The bounded command queue between `ProjectorControl` handles and the
+//! engine.
+//!
+//! This is a plain mutex-protected deque rather than a channel so that
+//! overflow policies can evict queued commands, and so the queue's contents
+//! can be inspected while the engine is busy.
+
+use std::collections::VecDeque;
+use std::sync::{Condvar, Mutex, MutexGuard};
+use std::task::{Context, Poll, Waker};
+use std::time::{Duration, Instant};
+
+use futures::future;
+use log::debug;
+
+use crate::{Command, Error};
+use crate::engine::SubmittedCommand;
+
+/// What to do when a command is submitted to a full queue.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum QueuePolicy {
+  /// Fail immediately with [`Error::QueueFull`].
+  Reject,
+
+  /// Wait until there is room. The command is only queued once the returned
+  /// future is polled.
+  #[default]
+  Wait,
+
+  /// Evict the oldest background command (see
+  /// [`submit_background`](crate::ProjectorControl::submit_background)),
+  /// which fails with [`Error::Evicted`]. If no background commands are queued
+  /// the new command is rejected.
+  DropOldestBackground,
+}
+
+/// A command that is currently executing.
+#[derive(Debug, Clone)]
+pub struct ActiveCommand {
+  pub command: Command,
+
+  /// Time since the engine picked up the command, including any time spent
+  /// waiting out the previous command's delay
+  pub elapsed: Duration,
+}
+
+/// A snapshot of the command queue.
+#[derive(Debug, Clone)]
+pub struct QueueStatus {
+  /// Number of commands waiting to be executed
+  pub depth: usize,
+
+  /// Maximum number of waiting commands
+  pub capacity: usize,
+
+  /// The command being executed, if any
+  pub current: Option<ActiveCommand>,
+
+  /// Time left before the projector may be sent another command, e.g. after
+  /// powering on
+  pub paused_for: Option<Duration>,
+}
+
+/// Serializes as `{"command": "pow=?", "elapsed_ms": 12}`. The command is
+/// formatted rather than serialized, as it may be an internal one such as
+/// [`Command::Sleep`].
+#[cfg(feature = "serde")]
+impl serde::Serialize for ActiveCommand {
+  fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    use serde::ser::SerializeStruct;
+
+    let mut s = serializer.serialize_struct("ActiveCommand", 2)?;
+    s.serialize_field("command", &self.command.to_string())?;
+    s.serialize_field("elapsed_ms", &(self.elapsed.as_millis() as u64))?;
+    s.end()
+  }
+}
+
+/// Serializes as `{"depth": 1, "capacity": 32, "current": ..., "paused_ms":
+/// 500}`, with `null` for no current command or pause.
+#[cfg(feature = "serde")]
+impl serde::Serialize for QueueStatus {
+  fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    use serde::ser::SerializeStruct;
+
+    let mut s = serializer.serialize_struct("QueueStatus", 4)?;
+    s.serialize_field("depth", &self.depth)?;
+    s.serialize_field("capacity", &self.capacity)?;
+    s.serialize_field("current", &self.current)?;
+    s.serialize_field("paused_ms", &self.paused_for.map(|d| d.as_millis() as u64))?;
+    s.end()
+  }
+}
+
+pub(crate) enum Push {
+  Queued,
+
+  /// The queue is full and the policy is to wait
+  Full(SubmittedCommand),
+
+  Rejected(Error),
+}
+
+/// Identifies a push waiting for room, so that polling it again replaces its
+/// waker instead of adding another.
+#[derive(Default)]
+pub(crate) struct SendSlot(Option<u64>);
+
+struct State {
+  items: VecDeque<SubmittedCommand>,
+  capacity: usize,
+  policy: QueuePolicy,
+
+  /// Set once every handle has been dropped; the engine drains what's left
+  /// and exits
+  released: bool,
+
+  /// Set once the engine has exited; nothing more can be queued
+  closed: bool,
+
+  recv_waker: Option<Waker>,
+
+  /// Wakers of pushes waiting for room, keyed by their [`SendSlot`]
+  send_wakers: Vec<(u64, Waker)>,
+  next_slot: u64,
+
+  current: Option<(Command, Instant)>,
+  ready_at: Option<Instant>,
+}
+
+pub(crate) struct Queue {
+  state: Mutex<State>,
+
+  /// Notifies the blocking engine that a command is available
+  available: Condvar,
+}
+
+impl Queue {
+  pub(crate) fn new(capacity: usize, policy: QueuePolicy) -> Queue {
+    Queue {
+      state: Mutex::new(State {
+        items: VecDeque::new(),
+        capacity,
+        policy,
+        released: false,
+        closed: false,
+        recv_waker: None,
+        send_wakers: Vec::new(),
+        next_slot: 0,
+        current: None,
+        ready_at: None,
+      }),
+      available: Condvar::new(),
+    }
+  }
+
+  fn lock(&self) -> MutexGuard<'_, State> {
+    self.state.lock().unwrap()
+  }
+
+  fn notify_receiver(&self, state: &mut State) {
+    self.available.notify_all();
+    if let Some(waker) = state.recv_waker.take() {
+      waker.wake();
+    }
+  }
+
+  fn notify_senders(state: &mut State) {
+    for (_, waker) in state.send_wakers.drain(..) {
+      waker.wake();
+    }
+  }
+
+  /// Queues `cmd`, applying the overflow policy if the queue is full. With
+  /// [`QueuePolicy::Wait`], `waker` is woken once there may be room; it
+  /// replaces any waker stored earlier for the same slot.
+  ///
+  /// `Stop` is always queued regardless of capacity.
+  pub(crate) fn push(&self, cmd: SubmittedCommand, waker: Option<(&mut SendSlot, &Waker)>) -> Push {
+    let mut state = self.lock();
+
+    if state.closed {
+      return Push::Rejected(Error::CommandSendError { command: cmd.command });
+    }
+
+    let full = state.items.len() >= state.capacity && !matches!(cmd.command, Command::Stop);
+    if full {
+      match state.policy {
+        QueuePolicy::Reject => {
+          return Push::Rejected(Error::QueueFull { command: cmd.command });
+        },
+
+        QueuePolicy::Wait => {
+          if let Some((slot, waker)) = waker {
+            let next_slot = &mut state.next_slot;
+            let id = *slot.0.get_or_insert_with(|| {
+              *next_slot += 1;
+              *next_slot
+            });
+
+            match state.send_wakers.iter_mut().find(|(i, _)| *i == id) {
+              Some((_, stored)) => stored.clone_from(waker),
+              None => state.send_wakers.push((id, waker.clone())),
+            }
+          }
+
+          return Push::Full(cmd);
+        },
+
+        QueuePolicy::DropOldestBackground => {
+          match state.items.iter().position(|c| c.background) {
+            Some(i) => {
+              let evicted = state.items.remove(i).unwrap();
+              debug!("queue full, evicting {:?}", &evicted.command);
+
+              let command = evicted.command.clone();
+              let _ = evicted.tx.send(Err(Error::Evicted { command }));
+            },
+            None => return Push::Rejected(Error::QueueFull { command: cmd.command })
+          }
+        }
+      }
+    }
+
+    state.items.push_back(cmd);
+    self.notify_receiver(&mut state);
+
+    Push::Queued
+  }
+
+  /// Retries a push that found the queue full. `cmd` is taken once queued.
+  pub(crate) fn poll_push(
+    &self,
+    cx: &mut Context<'_>,
+    cmd: &mut Option<SubmittedCommand>,
+    slot: &mut SendSlot
+  ) -> Poll<crate::Result<()>> {
+    let pending = match cmd.take() {
+      Some(pending) => pending,
+      None => return Poll::Ready(Ok(()))
+    };
+
+    match self.push(pending, Some((slot, cx.waker()))) {
+      Push::Queued => Poll::Ready(Ok(())),
+      Push::Rejected(e) => Poll::Ready(Err(e)),
+      Push::Full(pending) => {
+        *cmd = Some(pending);
+        Poll::Pending
+      }
+    }
+  }
+
+  fn try_pop(&self, state: &mut State) -> Option<Option<SubmittedCommand>> {
+    if let Some(cmd) = state.items.pop_front() {
+      Self::notify_senders(state);
+      Some(Some(cmd))
+    } else if state.released {
+      Some(None)
+    } else {
+      None
+    }
+  }
+
+  /// Waits for the next command, returning `None` once every handle has been
+  /// dropped and the queue is empty.
+  pub(crate) fn blocking_pop(&self) -> Option<SubmittedCommand> {
+    let mut state = self.lock();
+    loop {
+      if let Some(cmd) = self.try_pop(&mut state) {
+        return cmd;
+      }
+
+      state = self.available.wait(state).unwrap();
+    }
+  }
+
+  /// Async version of [`blocking_pop`](Self::blocking_pop).
+  pub(crate) async fn pop(&self) -> Option<SubmittedCommand> {
+    future::poll_fn(|cx| {
+      let mut state = self.lock();
+      match self.try_pop(&mut state) {
+        Some(cmd) => Poll::Ready(cmd),
+        None => {
+          state.recv_waker = Some(cx.waker().clone());
+          Poll::Pending
+        }
+      }
+    }).await
+  }
+
+  /// Returns `true` once every handle has been dropped.
+  pub(crate) fn is_released(&self) -> bool {
+    self.lock().released
+  }
+
+  /// Waits up to `timeout` for every handle to be dropped, returning `true`
+  /// if they have been.
+  pub(crate) fn wait_released(&self, timeout: Duration) -> bool {
+    let deadline = Instant::now() + timeout;
+    let mut state = self.lock();
+
+    while !state.released {
+      let now = Instant::now();
+      if now >= deadline {
+        return false;
+      }
+
+      state = self.available.wait_timeout(state, deadline - now).unwrap().0;
+    }
+
+    true
+  }
+
+  /// Completes once every handle has been dropped. Only the engine may call
+  /// this, and not while it is waiting in [`pop`](Self::pop).
+  pub(crate) async fn released(&self) {
+    future::poll_fn(|cx| {
+      let mut state = self.lock();
+      if state.released {
+        Poll::Ready(())
+      } else {
+        state.recv_waker = Some(cx.waker().clone());
+        Poll::Pending
+      }
+    }).await
+  }
+
+  /// Marks every handle as dropped.
+  pub(crate) fn release(&self) {
+    let mut state = self.lock();
+    state.released = true;
+    self.notify_receiver(&mut state);
+  }
+
+  /// Marks the engine as exited, failing anything still queued with
+  /// [`Error::CommandSendError`].
+  pub(crate) fn close(&self) {
+    let mut state = self.lock();
+    state.closed = true;
+    state.current = None;
+
+    for cmd in state.items.drain(..) {
+      debug!("engine exited, failing queued command {:?}", &cmd.command);
+
+      let command = cmd.command.clone();
+      let _ = cmd.tx.send(Err(Error::CommandSendError { command }));
+    }
+
+    Self::notify_senders(&mut state);
+  }
+
+  pub(crate) fn set_current(&self, command: Option<&Command>) {
+    self.lock().current = command.map(|c| (c.clone(), Instant::now()));
+  }
+
+  pub(crate) fn set_ready_at(&self, ready_at: Instant) {
+    self.lock().ready_at = Some(ready_at);
+  }
+
+  pub(crate) fn status(&self) -> QueueStatus {
+    let state = self.lock();
+
+    QueueStatus {
+      depth: state.items.len(),
+      capacity: state.capacity,
+      current: state.current.as_ref().map(|(command, started)| ActiveCommand {
+        command: command.clone(),
+        elapsed: started.elapsed(),
+      }),
+      paused_for: state.ready_at
+        .and_then(|r| r.checked_duration_since(Instant::now()))
+        .filter(|d| !d.is_zero()),
+    }
+  }
+}
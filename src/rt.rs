@@ -0,0 +1,53 @@
+//! Runtime backends for timers.
+//!
+//! The library itself doesn't need a particular executor; the backend only
+//! determines which timer implementation the async engine uses:
+//!
+//!  * `rt-tokio` (default): tokio's timers. The async engine must then run
+//!    within a tokio runtime.
+//!  * `rt-futures`: `futures-timer`, which works on any executor (async-std,
+//!    smol, ...).
+//!
+//! If both are enabled, tokio is used.
+
+use std::future::Future;
+use std::time::Duration;
+
+#[cfg(not(any(feature = "rt-tokio", feature = "rt-futures")))]
+compile_error!("one of the `rt-tokio` or `rt-futures` features must be enabled");
+
+#[cfg(feature = "rt-tokio")]
+mod imp {
+  use super::*;
+
+  pub(crate) async fn sleep(duration: Duration) {
+    tokio::time::sleep(duration).await
+  }
+
+  pub(crate) async fn timeout<F: Future>(duration: Duration, f: F) -> Option<F::Output> {
+    tokio::time::timeout(duration, f).await.ok()
+  }
+}
+
+#[cfg(all(feature = "rt-futures", not(feature = "rt-tokio")))]
+mod imp {
+  use super::*;
+
+  use futures::future::{self, Either};
+  use futures_timer::Delay;
+
+  pub(crate) async fn sleep(duration: Duration) {
+    Delay::new(duration).await
+  }
+
+  pub(crate) async fn timeout<F: Future>(duration: Duration, f: F) -> Option<F::Output> {
+    futures::pin_mut!(f);
+
+    match future::select(f, Delay::new(duration)).await {
+      Either::Left((output, _)) => Some(output),
+      Either::Right(_) => None
+    }
+  }
+}
+
+pub(crate) use imp::*;
@@ -0,0 +1,211 @@
+//! Scripted sequences of commands, e.g. a "movie night" macro.
+//!
+//! A [`Sequence`] is a list of [`Step`]s: commands, waits, power changes that
+//! wait for the projector to be ready, and steps that only run if a setting
+//! currently has a given value. Run one with
+//! [`ProjectorControl::run_sequence`](crate::ProjectorControl::run_sequence).
+//!
+//! ```
+//! use std::time::Duration;
+//! use benq_control::PowerState;
+//! use benq_control::sequence::Sequence;
+//!
+//! let movie_night = Sequence::new()
+//!   .when("pow", "off", Sequence::new().power(PowerState::On))
+//!   .command(("sour", "hdmi2"))
+//!   .wait(Duration::from_secs(2))
+//!   .command(("appmod", "cine"))
+//!   .command(("vol", "8"));
+//! ```
+//!
+//! With the `serde` feature, sequences can be loaded from files. Commands use
+//! the same syntax as [`Command`]'s `FromStr` and waits are in seconds:
+//!
+//! ```json
+//! {
+//!   "on_error": "continue",
+//!   "steps": [
+//!     {"when": {"key": "pow", "equals": "off", "steps": [{"power": "on"}]}},
+//!     {"command": "sour=hdmi2"},
+//!     {"wait": 2},
+//!     {"command": "appmod=cine"}
+//!   ]
+//! }
+//! ```
+
+use std::fmt;
+use std::time::Duration;
+
+use crate::{Command, Error, PowerState};
+
+/// What to do when a step fails.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum OnError {
+  /// Skip the remaining steps (the default)
+  #[default]
+  Stop,
+
+  /// Carry on with the next step
+  Continue,
+}
+
+/// One step of a [`Sequence`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum Step {
+  /// Executes a command. [`Command::Stop`] and [`Command::Sleep`] fail with
+  /// [`Error::InvalidCommand`]; use [`Step::Wait`] to pause.
+  Command(Command),
+
+  /// Holds the command queue for a while
+  Wait(#[cfg_attr(feature = "serde", serde(with = "seconds"))] Duration),
+
+  /// Changes the power state and waits until the projector is ready, using
+  /// [`PowerWait::default`](crate::PowerWait::default)
+  Power(PowerState),
+
+  /// Runs `steps` only if querying `key` returns `equals`
+  /// (case-insensitively)
+  When {
+    key: String,
+    equals: String,
+    steps: Vec<Step>,
+  },
+}
+
+impl fmt::Display for Step {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Step::Command(command) => write!(f, "{}", command),
+      Step::Wait(duration) => write!(f, "wait {:?}", duration),
+      Step::Power(state) => write!(f, "power {}", state),
+      Step::When { key, equals, .. } => write!(f, "when {}={}", key, equals),
+    }
+  }
+}
+
+/// A list of steps to run in order. See the [module docs](self).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Sequence {
+  #[cfg_attr(feature = "serde", serde(default))]
+  pub on_error: OnError,
+
+  pub steps: Vec<Step>,
+}
+
+impl Sequence {
+  pub fn new() -> Sequence {
+    Sequence::default()
+  }
+
+  /// Sets what to do when a step fails.
+  pub fn on_error(mut self, on_error: OnError) -> Self {
+    self.on_error = on_error;
+    self
+  }
+
+  pub fn step(mut self, step: Step) -> Self {
+    self.steps.push(step);
+    self
+  }
+
+  pub fn command(self, command: impl Into<Command>) -> Self {
+    self.step(Step::Command(command.into()))
+  }
+
+  pub fn wait(self, duration: Duration) -> Self {
+    self.step(Step::Wait(duration))
+  }
+
+  pub fn power(self, state: PowerState) -> Self {
+    self.step(Step::Power(state))
+  }
+
+  /// Adds the steps of `then`, to be run only if querying `key` returns
+  /// `equals`. The `on_error` setting of `then` is ignored.
+  pub fn when(self, key: &str, equals: &str, then: Sequence) -> Self {
+    self.step(Step::When {
+      key: key.to_string(),
+      equals: equals.to_string(),
+      steps: then.steps,
+    })
+  }
+}
+
+/// How a step turned out.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum StepOutcome {
+  /// The step succeeded, with the projector's response if there was one
+  Done(Option<String>),
+
+  /// A `when` step whose condition didn't hold
+  Skipped,
+
+  Failed(Error),
+}
+
+/// The outcome of a single step.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct StepReport {
+  /// The step, formatted for display
+  pub step: String,
+
+  /// How deeply the step is nested within `when` steps
+  pub depth: usize,
+
+  pub outcome: StepOutcome,
+}
+
+impl fmt::Display for StepReport {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{:indent$}{}: ", "", self.step, indent = self.depth * 2)?;
+
+    match &self.outcome {
+      StepOutcome::Done(Some(response)) => write!(f, "ok ({})", response),
+      StepOutcome::Done(None) => write!(f, "ok"),
+      StepOutcome::Skipped => write!(f, "skipped"),
+      StepOutcome::Failed(e) => write!(f, "failed: {}", e),
+    }
+  }
+}
+
+/// The outcome of running a [`Sequence`], one entry per step reached.
+#[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct SequenceReport {
+  pub steps: Vec<StepReport>,
+
+  /// Whether the sequence stopped early after a failure
+  pub stopped: bool,
+}
+
+impl SequenceReport {
+  /// Returns true if no step failed.
+  pub fn is_ok(&self) -> bool {
+    !self.steps.iter().any(|s| matches!(s.outcome, StepOutcome::Failed(_)))
+  }
+}
+
+/// (De)serializes a duration as a number of seconds.
+#[cfg(feature = "serde")]
+mod seconds {
+  use std::time::Duration;
+
+  use serde::{de, Deserialize, Deserializer, Serializer};
+
+  pub fn serialize<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(duration.as_secs_f64())
+  }
+
+  pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+    let seconds = f64::deserialize(deserializer)?;
+    Duration::try_from_secs_f64(seconds).map_err(de::Error::custom)
+  }
+}
@@ -0,0 +1,105 @@
+//! Saving and restoring projector settings.
+//!
+//! A [`Snapshot`] holds the raw value of every persistent setting a model
+//! can read back, keyed by command, e.g. `appmod` → `cine`. Take one with
+//! [`ProjectorControl::snapshot`](crate::ProjectorControl::snapshot) and apply
+//! it to the same or another projector with
+//! [`ProjectorControl::restore`](crate::ProjectorControl::restore). With the
+//! `serde` feature, snapshots can be serialized to JSON, TOML and so on.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::{Command, Error};
+use crate::profile::ModelProfile;
+
+/// The persistent settings of a projector.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Snapshot {
+  /// The model the snapshot was taken from
+  pub model: String,
+
+  /// Setting values keyed by command, e.g. `bri` → `50`
+  pub settings: BTreeMap<String, String>,
+}
+
+impl Snapshot {
+  /// Returns the settings that differ between this snapshot and `other`.
+  pub fn diff(&self, other: &Snapshot) -> Vec<SettingDiff> {
+    let mut keys: Vec<&String> = self.settings.keys().chain(other.settings.keys()).collect();
+    keys.sort();
+    keys.dedup();
+
+    keys.into_iter()
+      .filter_map(|key| {
+        let left = self.settings.get(key);
+        let right = other.settings.get(key);
+
+        match (left, right) {
+          (Some(l), Some(r)) if l.eq_ignore_ascii_case(r) => None,
+          _ => Some(SettingDiff {
+            key: key.clone(),
+            left: left.cloned(),
+            right: right.cloned(),
+          })
+        }
+      })
+      .collect()
+  }
+
+  /// Returns the commands that apply this snapshot, in the order given by
+  /// [`ModelProfile::settings`] followed by any other keys.
+  pub fn commands(&self, profile: &ModelProfile) -> Vec<Command> {
+    let ordered = profile.settings();
+    let first = ordered.iter().filter_map(|key| self.settings.get_key_value(*key));
+    let rest = self.settings.iter().filter(|(key, _)| !ordered.contains(&key.as_str()));
+
+    first.chain(rest)
+      .map(|(key, value)| Command::from((key.as_str(), value.as_str())))
+      .collect()
+  }
+}
+
+/// A setting that differs between two snapshots.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SettingDiff {
+  pub key: String,
+
+  /// The value in the first snapshot, if it has the setting
+  pub left: Option<String>,
+
+  /// The value in the second snapshot, if it has the setting
+  pub right: Option<String>,
+}
+
+impl fmt::Display for SettingDiff {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(
+      f,
+      "{}: {} -> {}",
+      self.key,
+      self.left.as_deref().unwrap_or("(unset)"),
+      self.right.as_deref().unwrap_or("(unset)")
+    )
+  }
+}
+
+/// The outcome of restoring a [`Snapshot`].
+#[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct RestoreReport {
+  /// Keys that were set successfully
+  pub applied: Vec<String>,
+
+  /// Keys that could not be set, with the reason
+  pub failed: Vec<(String, Error)>,
+}
+
+impl RestoreReport {
+  /// Returns true if every setting was applied.
+  pub fn is_ok(&self) -> bool {
+    self.failed.is_empty()
+  }
+}
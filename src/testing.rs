@@ -0,0 +1,333 @@
+//! Test support for code built on this crate, enabled with the `testing`
+//! feature.
+//!
+//! [`MockProjector`] is a scripted fake projector that speaks the serial
+//! protocol, so it exercises the same framing and queueing code as a real
+//! port:
+//!
+//! ```
+//! use benq_control::{Pacing, PowerState, ProjectorControl};
+//! use benq_control::testing::{MockProjector, Reply};
+//!
+//! let mock = MockProjector::new()
+//!   .expect("pow", Reply::response("POW=OFF"))
+//!   .expect(("pow", "on"), Reply::response("POW=ON"));
+//! let handle = mock.handle();
+//!
+//! let projector = ProjectorControl::builder()
+//!   .pacing(Pacing::none())
+//!   .build_blocking(mock);
+//!
+//! assert_eq!(projector.power()?, PowerState::Off);
+//! projector.set_power(PowerState::On)?;
+//!
+//! handle.assert_done();
+//! # Ok::<(), benq_control::Error>(())
+//! ```
+//!
+//! The mock is also an [`AsyncTransport`] for use with
+//! [`build_async`](crate::ProjectorControlBuilder::build_async).
+
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use futures::FutureExt;
+use futures::future::{self, BoxFuture};
+use serialport::ClearBuffer;
+
+use crate::{rt, Command, Result};
+use crate::protocol::{AsyncTransport, Transport};
+
+/// How the mock responds to an expected command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Reply {
+  /// Echo the command and answer with this frame body, e.g. `POW=ON`
+  Response(String),
+
+  /// Echo the command and refuse it with `*Block item#`
+  BlockItem,
+
+  /// Don't print the prompt when woken for this command. With the default
+  /// [`PromptMode::Required`](crate::protocol::PromptMode::Required) the
+  /// command is never sent and fails with
+  /// [`Error::CommandSendInvalidState`](crate::Error::CommandSendInvalidState).
+  NoPrompt,
+
+  /// Accept the command but send nothing back at all, not even the echo, as
+  /// if the projector had hung
+  Silence,
+
+  /// Fail the write with a broken pipe, as if the adapter were unplugged
+  Disconnect,
+}
+
+impl Reply {
+  pub fn response(body: impl Into<String>) -> Reply {
+    Reply::Response(body.into())
+  }
+}
+
+#[derive(Debug)]
+struct MockState {
+  expected: VecDeque<(Command, Reply)>,
+
+  /// Commands received, in order
+  received: Vec<Command>,
+
+  /// Commands received that didn't match the next expectation
+  unexpected: Vec<Command>,
+
+  echo: bool,
+  written: Vec<u8>,
+  pending: VecDeque<u8>,
+}
+
+impl MockState {
+  fn push(&mut self, bytes: &[u8]) {
+    self.pending.extend(bytes);
+  }
+
+  fn wake(&mut self) {
+    if let Some((_, Reply::NoPrompt)) = self.expected.front() {
+      self.expected.pop_front();
+      return;
+    }
+
+    self.push(b">");
+  }
+
+  fn command(&mut self, line: &[u8]) -> io::Result<()> {
+    let body = line.strip_prefix(b"*")
+      .and_then(|l| l.strip_suffix(b"#"))
+      .map(String::from_utf8_lossy)
+      .unwrap_or_default();
+
+    let command = match body.split_once('=') {
+      Some((key, "?")) => Command::Get(key.to_string()),
+      Some((key, value)) => Command::Set((key.to_string(), value.to_string())),
+      None => Command::Action(body.to_string()),
+    };
+
+    self.received.push(command.clone());
+
+    let reply = match self.expected.front() {
+      Some((expected, _)) if commands_match(expected, &command) => {
+        self.expected.pop_front().unwrap().1
+      },
+      _ => {
+        self.unexpected.push(command);
+        Reply::BlockItem
+      }
+    };
+
+    let echo = |state: &mut MockState| if state.echo {
+      state.push(line);
+      state.push(b"\r\n");
+    };
+
+    match reply {
+      Reply::Response(body) => {
+        echo(self);
+        self.push(format!("*{}#\r\n", body).as_bytes());
+      },
+      Reply::BlockItem => {
+        echo(self);
+        self.push(b"*Block item#\r\n");
+      },
+      Reply::NoPrompt | Reply::Silence => (),
+      Reply::Disconnect => return Err(io::ErrorKind::BrokenPipe.into()),
+    }
+
+    Ok(())
+  }
+}
+
+fn commands_match(a: &Command, b: &Command) -> bool {
+  match (a, b) {
+    (Command::Get(a), Command::Get(b)) => a.eq_ignore_ascii_case(b),
+    (Command::Action(a), Command::Action(b)) => a.eq_ignore_ascii_case(b),
+    (Command::Set((ak, av)), Command::Set((bk, bv))) => {
+      ak.eq_ignore_ascii_case(bk) && av.eq_ignore_ascii_case(bv)
+    },
+    _ => false
+  }
+}
+
+/// A scripted fake projector. See the [module docs](self).
+///
+/// Commands that don't match the next expectation are refused with
+/// `Block item` and reported by [`MockHandle::assert_done`].
+///
+/// Clones share the same script, so a clone can stand in for the reopened
+/// port after a [`Reply::Disconnect`] (see
+/// [`ProjectorControlBuilder::reconnect`](crate::ProjectorControlBuilder::reconnect)).
+#[derive(Clone)]
+pub struct MockProjector {
+  state: Arc<Mutex<MockState>>,
+}
+
+impl Default for MockProjector {
+  fn default() -> Self {
+    MockProjector::new()
+  }
+}
+
+impl MockProjector {
+  pub fn new() -> MockProjector {
+    MockProjector {
+      state: Arc::new(Mutex::new(MockState {
+        expected: VecDeque::new(),
+        received: Vec::new(),
+        unexpected: Vec::new(),
+        echo: true,
+        written: Vec::new(),
+        pending: VecDeque::new(),
+      }))
+    }
+  }
+
+  /// Expects `command` next, answering with `reply`.
+  pub fn expect(self, command: impl Into<Command>, reply: Reply) -> Self {
+    self.lock().expected.push_back((command.into(), reply));
+    self
+  }
+
+  /// Sets whether commands are echoed back, as most firmware does (the
+  /// default).
+  pub fn echo(self, echo: bool) -> Self {
+    self.lock().echo = echo;
+    self
+  }
+
+  /// Returns a handle for inspecting the mock once it has been moved into a
+  /// `ProjectorControl`.
+  pub fn handle(&self) -> MockHandle {
+    MockHandle { state: Arc::clone(&self.state) }
+  }
+
+  fn lock(&self) -> MutexGuard<'_, MockState> {
+    self.state.lock().unwrap()
+  }
+}
+
+impl Read for MockProjector {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    let mut state = self.lock();
+    if state.pending.is_empty() {
+      return Err(io::ErrorKind::TimedOut.into());
+    }
+
+    let n = buf.len().min(state.pending.len());
+    for (b, p) in buf.iter_mut().zip(state.pending.drain(..n)) {
+      *b = p;
+    }
+
+    Ok(n)
+  }
+}
+
+impl Write for MockProjector {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    let mut state = self.lock();
+
+    for &b in buf {
+      if b == b'\n' {
+        // \r\n line endings
+        continue;
+      }
+
+      if b != b'\r' {
+        state.written.push(b);
+        continue;
+      }
+
+      let line = std::mem::take(&mut state.written);
+      if line.is_empty() {
+        state.wake();
+      } else {
+        state.command(&line)?;
+      }
+    }
+
+    Ok(buf.len())
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    Ok(())
+  }
+}
+
+impl Transport for MockProjector {
+  fn clear(&mut self, buffer: ClearBuffer) -> Result<()> {
+    if let ClearBuffer::Input | ClearBuffer::All = buffer {
+      self.lock().pending.clear();
+    }
+
+    Ok(())
+  }
+}
+
+impl AsyncTransport for MockProjector {
+  fn read<'a>(&'a mut self, buf: &'a mut [u8]) -> BoxFuture<'a, io::Result<usize>> {
+    async move {
+      let result = Read::read(self, buf);
+
+      // replies are available as soon as a command is written, so if there's
+      // nothing yet, yield briefly rather than spin
+      if result.is_err() {
+        rt::sleep(Duration::from_millis(1)).await;
+      }
+
+      result
+    }.boxed()
+  }
+
+  fn write_all<'a>(&'a mut self, buf: &'a [u8]) -> BoxFuture<'a, io::Result<()>> {
+    future::ready(Write::write_all(self, buf)).boxed()
+  }
+
+  fn clear(&mut self, buffer: ClearBuffer) -> Result<()> {
+    Transport::clear(self, buffer)
+  }
+}
+
+/// Inspects a [`MockProjector`] after it has been handed over.
+#[derive(Clone)]
+pub struct MockHandle {
+  state: Arc<Mutex<MockState>>,
+}
+
+impl MockHandle {
+  /// Returns every command received so far, in order.
+  pub fn commands(&self) -> Vec<Command> {
+    self.state.lock().unwrap().received.clone()
+  }
+
+  /// Adds another expectation, e.g. after some commands have been checked.
+  pub fn expect(&self, command: impl Into<Command>, reply: Reply) {
+    self.state.lock().unwrap().expected.push_back((command.into(), reply));
+  }
+
+  /// Panics unless every expected command was received, in order, and
+  /// nothing else was.
+  #[track_caller]
+  pub fn assert_done(&self) {
+    let state = self.state.lock().unwrap();
+
+    assert!(
+      state.unexpected.is_empty(),
+      "mock projector received unexpected commands: {:?} (all commands: {:?})",
+      state.unexpected,
+      state.received
+    );
+
+    assert!(
+      state.expected.is_empty(),
+      "mock projector never received: {:?} (received: {:?})",
+      state.expected.iter().map(|(c, _)| c).collect::<Vec<_>>(),
+      state.received
+    );
+  }
+}
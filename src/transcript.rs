@@ -0,0 +1,341 @@
+//! Recording and replaying sessions with a projector.
+//!
+//! [`Recorder`] wraps a transport and logs every chunk of bytes read or
+//! written, with timing, to a transcript. [`Replay`] plays a transcript back
+//! as a fake transport, so sessions captured from real projectors can be used
+//! in tests without any hardware.
+//!
+//! Transcripts are plain text with one chunk per line: milliseconds since the
+//! start of the recording, `w` (written to the projector) or `r` (read from
+//! it), and the bytes in hex. Blank lines and lines starting with `#` are
+//! ignored.
+//!
+//! ```text
+//! # TH685, pow=?
+//! 0 w 0d
+//! 3 r 3e
+//! 4 w 2a706f773d3f230d
+//! 31 r 2a706f773d3f230d0a2a504f573d4f4e230d0a
+//! ```
+
+use std::fmt;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::str::FromStr;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use log::warn;
+use serialport::ClearBuffer;
+
+use crate::{Error, Result};
+use crate::protocol::Transport;
+
+/// The longest a realtime [`Replay`] blocks in a single read.
+const REALTIME_POLL_PERIOD: Duration = Duration::from_millis(10);
+
+/// The direction of a recorded chunk, from the host's point of view.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+  Read,
+  Write,
+}
+
+/// A chunk of bytes read or written.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Event {
+  /// Time since the start of the recording
+  pub at: Duration,
+  pub direction: Direction,
+  pub data: Vec<u8>,
+}
+
+impl fmt::Display for Event {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let direction = match self.direction {
+      Direction::Read => 'r',
+      Direction::Write => 'w',
+    };
+
+    write!(f, "{} {} ", self.at.as_millis(), direction)?;
+    for b in &self.data {
+      write!(f, "{:02x}", b)?;
+    }
+
+    Ok(())
+  }
+}
+
+fn parse_hex(s: &str) -> Option<Vec<u8>> {
+  if s.len() % 2 != 0 {
+    return None;
+  }
+
+  (0..s.len())
+    .step_by(2)
+    .map(|i| s.get(i..i + 2).and_then(|b| u8::from_str_radix(b, 16).ok()))
+    .collect()
+}
+
+impl FromStr for Event {
+  type Err = Error;
+
+  fn from_str(s: &str) -> Result<Self> {
+    let invalid = || Error::InvalidTranscript(s.to_string());
+
+    let mut parts = s.split_whitespace();
+    let at = parts.next()
+      .and_then(|at| at.parse::<u64>().ok())
+      .ok_or_else(invalid)?;
+
+    let direction = match parts.next() {
+      Some("r") => Direction::Read,
+      Some("w") => Direction::Write,
+      _ => return Err(invalid())
+    };
+
+    let data = parse_hex(parts.next().unwrap_or("")).ok_or_else(invalid)?;
+    if parts.next().is_some() {
+      return Err(invalid());
+    }
+
+    Ok(Event { at: Duration::from_millis(at), direction, data })
+  }
+}
+
+/// A recorded session.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Transcript {
+  pub events: Vec<Event>,
+}
+
+impl Transcript {
+  /// Loads a transcript from a file.
+  pub fn load(path: impl AsRef<Path>) -> Result<Transcript> {
+    fs::read_to_string(path)?.parse()
+  }
+}
+
+impl FromStr for Transcript {
+  type Err = Error;
+
+  fn from_str(s: &str) -> Result<Self> {
+    let events = s.lines()
+      .map(str::trim)
+      .filter(|line| !line.is_empty() && !line.starts_with('#'))
+      .map(str::parse)
+      .collect::<Result<_>>()?;
+
+    Ok(Transcript { events })
+  }
+}
+
+impl fmt::Display for Transcript {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    for event in &self.events {
+      writeln!(f, "{}", event)?;
+    }
+
+    Ok(())
+  }
+}
+
+/// Wraps a transport, writing everything that passes through it to `sink` in
+/// the transcript format.
+///
+/// Failing to write the transcript is logged but otherwise ignored so that
+/// recording never interferes with the session itself.
+pub struct Recorder<T, W> {
+  inner: T,
+  sink: W,
+  started: Instant,
+}
+
+impl<T, W: Write> Recorder<T, W> {
+  pub fn new(inner: T, sink: W) -> Recorder<T, W> {
+    Recorder { inner, sink, started: Instant::now() }
+  }
+
+  fn record(&mut self, direction: Direction, data: &[u8]) {
+    let event = Event {
+      at: self.started.elapsed(),
+      direction,
+      data: data.to_vec(),
+    };
+
+    if let Err(e) = writeln!(self.sink, "{}", event).and_then(|_| self.sink.flush()) {
+      warn!("could not write transcript: {}", e);
+    }
+  }
+
+  /// Returns the wrapped transport and transcript sink.
+  pub fn into_inner(self) -> (T, W) {
+    (self.inner, self.sink)
+  }
+}
+
+impl<T: Read, W: Write> Read for Recorder<T, W> {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    let n = self.inner.read(buf)?;
+    if n > 0 {
+      self.record(Direction::Read, &buf[..n]);
+    }
+
+    Ok(n)
+  }
+}
+
+impl<T: Write, W: Write> Write for Recorder<T, W> {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    let n = self.inner.write(buf)?;
+    self.record(Direction::Write, &buf[..n]);
+    Ok(n)
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    self.inner.flush()
+  }
+}
+
+impl<T: Transport, W: Write + Send> Transport for Recorder<T, W> {
+  fn clear(&mut self, buffer: ClearBuffer) -> Result<()> {
+    self.inner.clear(buffer)
+  }
+}
+
+/// A fake transport that plays back a [`Transcript`].
+///
+/// Writes must match the recording, though they may be split into chunks
+/// differently; a mismatch fails with [`io::ErrorKind::InvalidData`]. Recorded
+/// reads are only delivered once every write before them has been made, and
+/// reads with nothing available time out like an idle serial port.
+pub struct Replay {
+  events: Vec<Event>,
+  next: usize,
+
+  /// Bytes of the current event already consumed
+  offset: usize,
+
+  realtime: bool,
+
+  /// When the most recent write event was completed, and its recorded time
+  last_write: Option<(Instant, Duration)>,
+}
+
+impl Replay {
+  pub fn new(transcript: Transcript) -> Replay {
+    Replay {
+      events: transcript.events,
+      next: 0,
+      offset: 0,
+      realtime: false,
+      last_write: None,
+    }
+  }
+
+  /// Loads and plays back the transcript at `path`.
+  pub fn load(path: impl AsRef<Path>) -> Result<Replay> {
+    Ok(Replay::new(Transcript::load(path)?))
+  }
+
+  /// Delays each read until as long after the preceding write as it was
+  /// recorded, to reproduce slow or fragmented responses. By default reads
+  /// are delivered as soon as possible.
+  pub fn realtime(mut self, realtime: bool) -> Self {
+    self.realtime = realtime;
+    self
+  }
+
+  /// Returns `true` once every recorded event has been replayed.
+  pub fn is_finished(&self) -> bool {
+    self.next >= self.events.len()
+  }
+
+  fn advance(&mut self) {
+    self.next += 1;
+    self.offset = 0;
+  }
+}
+
+impl Read for Replay {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    let event = match self.events.get(self.next) {
+      Some(event) if event.direction == Direction::Read => event,
+      _ => return Err(io::ErrorKind::TimedOut.into())
+    };
+
+    // like a real port, time out if the data hasn't "arrived" yet
+    if let (true, Some((written, written_at))) = (self.realtime, self.last_write) {
+      let due = written + event.at.saturating_sub(written_at);
+      if let Some(wait) = due.checked_duration_since(Instant::now()) {
+        thread::sleep(wait.min(REALTIME_POLL_PERIOD));
+        return Err(io::ErrorKind::TimedOut.into());
+      }
+    }
+
+    let remaining = &event.data[self.offset..];
+    let n = remaining.len().min(buf.len());
+    buf[..n].copy_from_slice(&remaining[..n]);
+
+    self.offset += n;
+    if self.offset >= event.data.len() {
+      self.advance();
+    }
+
+    Ok(n)
+  }
+}
+
+impl Write for Replay {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    let mut written = 0;
+
+    while written < buf.len() {
+      let event = match self.events.get(self.next) {
+        Some(event) if event.direction == Direction::Write => event,
+        Some(_) => return Err(io::Error::new(
+          io::ErrorKind::InvalidData,
+          format!("unexpected write {:?}, expected a read", buf.escape_ascii().to_string())
+        )),
+        None => return Err(io::Error::new(
+          io::ErrorKind::InvalidData,
+          format!("unexpected write {:?} past end of transcript", buf.escape_ascii().to_string())
+        ))
+      };
+
+      let expected = &event.data[self.offset..];
+      let n = expected.len().min(buf.len() - written);
+      if expected[..n] != buf[written..written + n] {
+        return Err(io::Error::new(
+          io::ErrorKind::InvalidData,
+          format!(
+            "write {:?} does not match transcript, expected {:?}",
+            buf.escape_ascii().to_string(),
+            expected.escape_ascii().to_string()
+          )
+        ));
+      }
+
+      written += n;
+      self.offset += n;
+      if self.offset >= event.data.len() {
+        self.last_write = Some((Instant::now(), event.at));
+        self.advance();
+      }
+    }
+
+    Ok(written)
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    Ok(())
+  }
+}
+
+impl Transport for Replay {
+  fn clear(&mut self, _buffer: ClearBuffer) -> Result<()> {
+    // anything the original session discarded was never recorded
+    Ok(())
+  }
+}
@@ -0,0 +1,413 @@
+//! Typed values for common projector settings.
+//!
+//! The projector answers queries with `KEY=VALUE` response frames, e.g.
+//! `POW=ON` or `VOL=5`. These types parse the value half and format the
+//! argument for the matching set command.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::{Command, CommandResult, Error, Result};
+
+/// Extracts the value from a `KEY=VALUE` response, checking that the key
+/// matches (case-insensitively).
+pub fn response_value<'a>(key: &str, response: &'a Option<String>) -> Result<&'a str> {
+  let response = response.as_deref()
+    .ok_or_else(|| Error::ResponseUnexpectedFormat(format!("empty response to {}", key)))?;
+
+  match response.split_once('=') {
+    Some((k, v)) if k.eq_ignore_ascii_case(key) => Ok(v),
+    _ => Err(Error::ResponseUnexpectedFormat(response.to_string()))
+  }
+}
+
+/// Parses the value of a `KEY=VALUE` response to a query for `key`.
+pub fn parse_response<T: FromStr>(key: &str, result: CommandResult) -> Result<T> {
+  let response = result?;
+  let value = response_value(key, &response)?;
+
+  value.parse().map_err(|_| Error::ResponseUnexpectedFormat(value.to_string()))
+}
+
+/// Parses an `ON`/`OFF` response to a query for `key`.
+pub fn parse_switch(key: &str, result: CommandResult) -> Result<bool> {
+  let response = result?;
+  let value = response_value(key, &response)?;
+
+  match value.to_ascii_lowercase().as_str() {
+    "on" => Ok(true),
+    "off" => Ok(false),
+    _ => Err(Error::ResponseUnexpectedFormat(value.to_string()))
+  }
+}
+
+/// Formats a boolean as the `on`/`off` argument of a set command.
+pub fn switch(on: bool) -> &'static str {
+  if on { "on" } else { "off" }
+}
+
+/// The projector's power state.
+///
+/// Unlike the keyword enums below this has no `Other` variant: the projector
+/// only ever reports on or off, and keeping it `Copy` and exhaustive lets
+/// callers match on it directly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PowerState {
+  On,
+  Off,
+}
+
+impl FromStr for PowerState {
+  type Err = Error;
+
+  fn from_str(s: &str) -> Result<Self> {
+    match s.to_ascii_lowercase().as_str() {
+      "on" => Ok(PowerState::On),
+      "off" => Ok(PowerState::Off),
+      _ => Err(Error::InvalidValue(s.to_string()))
+    }
+  }
+}
+
+impl fmt::Display for PowerState {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      PowerState::On => write!(f, "on"),
+      PowerState::Off => write!(f, "off"),
+    }
+  }
+}
+
+/// Defines an enum of keyword values with a case-insensitive
+/// `FromStr` and a `Display` that formats the projector's keyword. The first
+/// keyword of each variant is canonical and any others are accepted aliases.
+/// Unknown keywords are passed through as `Other`.
+macro_rules! keyword_enum {
+  (
+    $(#[$meta:meta])*
+    pub enum $name:ident {
+      $(
+        $(#[$variant_meta:meta])*
+        $variant:ident = $keyword:literal $(| $alias:literal)*,
+      )*
+    }
+  ) => {
+    $(#[$meta])*
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub enum $name {
+      $(
+        $(#[$variant_meta])*
+        $variant,
+      )*
+
+      /// Any other value, as named by the projector
+      Other(String),
+    }
+
+    impl $name {
+      /// Every known value.
+      pub const KNOWN: &'static [$name] = &[$($name::$variant),*];
+    }
+
+    impl FromStr for $name {
+      type Err = Error;
+
+      fn from_str(s: &str) -> Result<Self> {
+        Ok(match s.to_ascii_lowercase().as_str() {
+          "" => return Err(Error::InvalidValue(s.to_string())),
+          $($keyword $(| $alias)* => $name::$variant,)*
+          other => $name::Other(other.to_string()),
+        })
+      }
+    }
+
+    impl fmt::Display for $name {
+      fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", match self {
+          $($name::$variant => $keyword,)*
+          $name::Other(s) => s,
+        })
+      }
+    }
+
+    serde_via_str!($name);
+  };
+}
+
+keyword_enum! {
+  /// An input source.
+  ///
+  /// Models differ in which inputs they have; sources not listed here are
+  /// passed through as [`Source::Other`].
+  pub enum Source {
+    Hdmi = "hdmi" | "hdmi1",
+    Hdmi2 = "hdmi2",
+    Rgb = "rgb",
+    Rgb2 = "rgb2",
+    Component = "ypbr",
+    Composite = "vid",
+    SVideo = "svid",
+    DviA = "dvia",
+    DviD = "dvid",
+    HdBaseT = "hdbaset",
+    Network = "network",
+    UsbDisplay = "usbdisplay",
+    UsbReader = "usbreader",
+  }
+}
+
+keyword_enum! {
+  /// A picture mode preset (`appmod`).
+  pub enum PictureMode {
+    Bright = "bright",
+    Vivid = "vivid",
+    Cinema = "cine" | "cinema",
+    Standard = "std" | "standard",
+    Presentation = "preset" | "presentation",
+    Dynamic = "dynamic",
+    LivingRoom = "livingroom",
+    Game = "game",
+    Sport = "sport",
+    ThreeD = "3d",
+    User1 = "user1" | "user",
+    User2 = "user2",
+    User3 = "user3",
+  }
+}
+
+keyword_enum! {
+  /// A colour temperature preset (`ct`).
+  pub enum ColorTemperature {
+    Warmer = "warmer",
+    Warm = "warm",
+    Normal = "normal",
+    Cool = "cool",
+    Cooler = "cooler",
+    Native = "native" | "lamp",
+  }
+}
+
+keyword_enum! {
+  /// A gamma setting (`gamma`).
+  pub enum Gamma {
+    G16 = "1.6",
+    G18 = "1.8",
+    G20 = "2.0",
+    G21 = "2.1",
+    G22 = "2.2",
+    G23 = "2.3",
+    G24 = "2.4",
+    G25 = "2.5",
+    G26 = "2.6",
+    G28 = "2.8",
+    Bt1886 = "bt1886",
+  }
+}
+
+keyword_enum! {
+  /// An aspect ratio mode (`asp`).
+  pub enum AspectRatio {
+    Auto = "auto",
+    Real = "real",
+    R4x3 = "4:3",
+    R16x9 = "16:9",
+    R16x10 = "16:10",
+    Letterbox = "lbox" | "letterbox",
+    Wide = "wide",
+    Anamorphic = "anam" | "anamorphic",
+  }
+}
+
+/// A picture adjustment with a numeric level.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PictureLevel {
+  Brightness,
+  Contrast,
+  Color,
+  Sharpness,
+}
+
+impl PictureLevel {
+  pub const ALL: [PictureLevel; 4] = [
+    PictureLevel::Brightness,
+    PictureLevel::Contrast,
+    PictureLevel::Color,
+    PictureLevel::Sharpness,
+  ];
+
+  /// Returns the key used to query and set this level.
+  pub fn key(&self) -> &'static str {
+    match self {
+      PictureLevel::Brightness => "bri",
+      PictureLevel::Contrast => "con",
+      PictureLevel::Color => "color",
+      PictureLevel::Sharpness => "sharp",
+    }
+  }
+}
+
+impl FromStr for PictureLevel {
+  type Err = Error;
+
+  fn from_str(s: &str) -> Result<Self> {
+    match s.to_ascii_lowercase().as_str() {
+      "brightness" | "bri" => Ok(PictureLevel::Brightness),
+      "contrast" | "con" => Ok(PictureLevel::Contrast),
+      "color" | "colour" => Ok(PictureLevel::Color),
+      "sharpness" | "sharp" => Ok(PictureLevel::Sharpness),
+      _ => Err(Error::InvalidValue(s.to_string()))
+    }
+  }
+}
+
+impl fmt::Display for PictureLevel {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}", match self {
+      PictureLevel::Brightness => "brightness",
+      PictureLevel::Contrast => "contrast",
+      PictureLevel::Color => "color",
+      PictureLevel::Sharpness => "sharpness",
+    })
+  }
+}
+
+keyword_enum! {
+  /// A lamp or light source power mode (`lampm`).
+  pub enum LampMode {
+    Normal = "lnor" | "normal",
+    Eco = "eco",
+    SmartEco = "seco" | "smarteco",
+  }
+}
+
+/// Lamp and filter wear, as reported by [`ProjectorControl::maintenance`](crate::ProjectorControl::maintenance).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MaintenanceStatus {
+  pub lamp_mode: LampMode,
+
+  /// Hours on each lamp or light source, in the order of the profile's
+  /// [`lamp_timers`](crate::ModelProfile::lamp_timers)
+  pub lamp_hours: Vec<u32>,
+
+  /// Hours since the filter was last cleaned, if the model has a filter timer
+  pub filter_hours: Option<u32>,
+}
+
+keyword_enum! {
+  /// A button on the remote control.
+  ///
+  /// Useful for driving the on-screen menu to reach settings that have no
+  /// serial command of their own.
+  pub enum RemoteKey {
+    /// Opens the menu
+    Menu = "menu",
+
+    /// Closes the menu
+    Exit = "exit",
+
+    Up = "up",
+    Down = "down",
+    Left = "left",
+    Right = "right",
+    Enter = "enter" | "ok",
+    Back = "back",
+
+    /// Re-syncs to the input signal
+    Auto = "auto",
+  }
+}
+
+impl From<&RemoteKey> for Command {
+  fn from(key: &RemoteKey) -> Command {
+    match key {
+      RemoteKey::Menu => Command::Set(("menu".to_string(), "on".to_string())),
+      RemoteKey::Exit => Command::Set(("menu".to_string(), "off".to_string())),
+      key => Command::Action(key.to_string()),
+    }
+  }
+}
+
+keyword_enum! {
+  /// A 3D format (`3d`).
+  pub enum ThreeDMode {
+    Off = "off",
+    Auto = "auto",
+    FrameSequential = "fs" | "frame-sequential",
+    TopBottom = "tb" | "top-bottom",
+    SideBySide = "sbs" | "side-by-side",
+    FramePacking = "fp" | "frame-packing",
+  }
+}
+
+keyword_enum! {
+  /// How the projector is mounted (`pp`), which decides how the picture is
+  /// flipped.
+  pub enum ProjectorPosition {
+    FrontTable = "ft" | "front-table",
+    RearTable = "re" | "rear-table",
+    RearCeiling = "rc" | "rear-ceiling",
+    FrontCeiling = "fc" | "front-ceiling",
+  }
+}
+
+keyword_enum! {
+  /// The on-screen menu language (`lang`).
+  pub enum Language {
+    English = "english",
+    French = "french",
+    German = "german",
+    Italian = "italian",
+    Spanish = "spanish",
+    Portuguese = "portuguese",
+    Dutch = "dutch",
+    Swedish = "swedish",
+    Polish = "polish",
+    Russian = "russian",
+    Turkish = "turkish",
+    Japanese = "japanese",
+    Korean = "korean",
+    TraditionalChinese = "tchinese",
+    SimplifiedChinese = "schinese",
+  }
+}
+
+/// The direction of a keystone correction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeystoneAxis {
+  Vertical,
+  Horizontal,
+}
+
+impl KeystoneAxis {
+  /// Returns the key used to query and set keystone on this axis.
+  pub fn key(&self) -> &'static str {
+    match self {
+      KeystoneAxis::Vertical => "vkeystone",
+      KeystoneAxis::Horizontal => "hkeystone",
+    }
+  }
+}
+
+impl FromStr for KeystoneAxis {
+  type Err = Error;
+
+  fn from_str(s: &str) -> Result<Self> {
+    match s.to_ascii_lowercase().as_str() {
+      "vertical" | "v" => Ok(KeystoneAxis::Vertical),
+      "horizontal" | "h" => Ok(KeystoneAxis::Horizontal),
+      _ => Err(Error::InvalidValue(s.to_string()))
+    }
+  }
+}
+
+impl fmt::Display for KeystoneAxis {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}", match self {
+      KeystoneAxis::Vertical => "vertical",
+      KeystoneAxis::Horizontal => "horizontal",
+    })
+  }
+}
+
+serde_via_str!(PowerState, PictureLevel, KeystoneAxis);
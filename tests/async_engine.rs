@@ -0,0 +1,68 @@
+//! Round trips through the async engine, run with whichever runtime backend
+//! is enabled: `cargo test --features testing` for tokio, or
+//! `cargo test --no-default-features --features rt-futures,testing`.
+
+use benq_control::{CommandEngine, Error, Pacing, PowerState, ProjectorControl, Source};
+use benq_control::testing::{MockProjector, Reply};
+use futures::FutureExt;
+
+fn start(mock: MockProjector) -> (ProjectorControl, CommandEngine) {
+  ProjectorControl::builder()
+    .pacing(Pacing::none())
+    .build_async(mock)
+}
+
+fn script() -> MockProjector {
+  MockProjector::new()
+    .expect("pow", Reply::response("POW=ON"))
+    .expect(("sour", "hdmi2"), Reply::response("SOUR=HDMI2"))
+    .expect("vol", Reply::BlockItem)
+}
+
+async fn round_trip(projector: ProjectorControl) {
+  assert_eq!(projector.power().await.unwrap(), PowerState::On);
+  projector.set_source(&Source::Hdmi2).await.unwrap();
+  assert!(matches!(projector.volume().await, Err(Error::ResponseBlockItem)));
+
+  projector.shutdown().await;
+}
+
+#[cfg(feature = "rt-tokio")]
+#[tokio::test]
+async fn round_trip_on_tokio() {
+  let mock = script();
+  let handle = mock.handle();
+
+  let (projector, engine) = start(mock);
+  let engine = tokio::spawn(engine);
+
+  round_trip(projector).await;
+  engine.await.unwrap();
+  handle.assert_done();
+}
+
+#[cfg(not(feature = "rt-tokio"))]
+#[test]
+fn round_trip_on_futures() {
+  let mock = script();
+  let handle = mock.handle();
+
+  let (projector, engine) = start(mock);
+  futures::executor::block_on(futures::future::join(engine, round_trip(projector)));
+  handle.assert_done();
+}
+
+#[test]
+fn dropping_the_engine_fails_queued_commands() {
+  let (projector, engine) = start(MockProjector::new());
+
+  let pow = projector.submit_command("pow");
+  drop(engine);
+
+  let result = pow.now_or_never();
+  assert!(matches!(result, Some(Err(Error::CommandSendError { .. }))), "{:?}", result);
+  assert!(matches!(
+    projector.submit_command("pow").now_or_never(),
+    Some(Err(Error::CommandSendError { .. }))
+  ));
+}
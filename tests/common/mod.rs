@@ -0,0 +1,87 @@
+// not every test uses every helper
+#![allow(dead_code)]
+
+use std::io::{self, Read, Write};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use benq_control::Result;
+use benq_control::protocol::Transport;
+use serialport::ClearBuffer;
+
+
+/// Polls `f` until it returns true, panicking after a few seconds.
+#[track_caller]
+pub fn wait_until(mut f: impl FnMut() -> bool) {
+  let deadline = Instant::now() + Duration::from_secs(5);
+  while !f() {
+    assert!(Instant::now() < deadline, "timed out waiting for condition");
+    thread::sleep(Duration::from_millis(1));
+  }
+}
+
+#[derive(Default)]
+struct GateState {
+  open: bool,
+  dropped: bool,
+}
+
+/// Holds the engine mid-command, so tests can inspect and fill the queue
+/// while it is busy.
+#[derive(Clone, Default)]
+pub struct Gate(Arc<(Mutex<GateState>, Condvar)>);
+
+impl Gate {
+  /// Wraps `inner` so that every write blocks until the gate is opened.
+  pub fn wrap<T: Transport>(&self, inner: T) -> Gated<T> {
+    Gated { inner, gate: self.clone() }
+  }
+
+  pub fn open(&self) {
+    self.0.0.lock().unwrap().open = true;
+    self.0.1.notify_all();
+  }
+
+  /// Returns true once the wrapped transport has been dropped, i.e. the
+  /// port has been closed.
+  pub fn is_dropped(&self) -> bool {
+    self.0.0.lock().unwrap().dropped
+  }
+}
+
+pub struct Gated<T> {
+  inner: T,
+  gate: Gate,
+}
+
+impl<T: Read> Read for Gated<T> {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    self.inner.read(buf)
+  }
+}
+
+impl<T: Write> Write for Gated<T> {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    let (state, opened) = &*self.gate.0;
+    let _open = opened.wait_while(state.lock().unwrap(), |s| !s.open).unwrap();
+
+    self.inner.write(buf)
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    self.inner.flush()
+  }
+}
+
+impl<T: Transport> Transport for Gated<T> {
+  fn clear(&mut self, buffer: ClearBuffer) -> Result<()> {
+    self.inner.clear(buffer)
+  }
+}
+
+impl<T> Drop for Gated<T> {
+  fn drop(&mut self) {
+    self.gate.0.0.lock().unwrap().dropped = true;
+  }
+}
@@ -0,0 +1,115 @@
+use std::io::{self, Read, Write};
+
+use benq_control::{Error, Result};
+use benq_control::detect::{self, LineSettings};
+use benq_control::protocol::{ProtocolOptions, Transport};
+use benq_control::testing::{MockProjector, Reply};
+use serialport::{ClearBuffer, Parity, StopBits};
+
+/// Either the projector, or a line opened with the wrong settings, which
+/// garbles everything written to it.
+enum Line {
+  Projector(MockProjector),
+  Garbled,
+}
+
+impl Read for Line {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    match self {
+      Line::Projector(mock) => mock.read(buf),
+      Line::Garbled => Err(io::ErrorKind::TimedOut.into()),
+    }
+  }
+}
+
+impl Write for Line {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    match self {
+      Line::Projector(mock) => mock.write(buf),
+      Line::Garbled => Err(io::ErrorKind::InvalidData.into()),
+    }
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    Ok(())
+  }
+}
+
+impl Transport for Line {
+  fn clear(&mut self, buffer: ClearBuffer) -> Result<()> {
+    match self {
+      Line::Projector(mock) => mock.clear(buffer),
+      Line::Garbled => Ok(()),
+    }
+  }
+}
+
+/// Opens the projector only with `actual`, recording every attempt.
+fn opener<'a>(
+  actual: LineSettings,
+  reply: Reply,
+  tried: &'a mut Vec<LineSettings>
+) -> impl FnMut(LineSettings) -> Result<Line> + 'a {
+  move |settings| {
+    tried.push(settings);
+
+    if settings == actual {
+      Ok(Line::Projector(MockProjector::new().expect("modelname", reply.clone())))
+    } else {
+      Ok(Line::Garbled)
+    }
+  }
+}
+
+#[test]
+fn detects_baud_rate_and_line_format() {
+  let actual = LineSettings {
+    parity: Parity::Even,
+    ..LineSettings::new(9_600)
+  };
+
+  let mut tried = Vec::new();
+  let open = opener(actual, Reply::response("MODELNAME=TH685"), &mut tried);
+  let detected = detect::detect_with("mock", &ProtocolOptions::default(), open).unwrap();
+
+  assert_eq!(detected.settings, actual);
+  assert_eq!(detected.settings.to_string(), "9600 8E1");
+  assert_eq!(detected.model.as_deref(), Some("TH685"));
+
+  // every rate at 8N1, then 8E1 from the top
+  assert_eq!(tried.len(), detect::BAUD_RATES.len() + 5);
+  assert_eq!(tried[0], LineSettings::new(115_200));
+  assert_eq!(tried[detect::BAUD_RATES.len()].parity, Parity::Even);
+  assert!(tried.iter().all(|s| s.stop_bits == StopBits::One));
+}
+
+#[test]
+fn block_item_counts_as_a_response() {
+  let actual = LineSettings::new(57_600);
+
+  let mut tried = Vec::new();
+  let open = opener(actual, Reply::BlockItem, &mut tried);
+  let detected = detect::detect_with("mock", &ProtocolOptions::default(), open).unwrap();
+
+  assert_eq!(detected.settings, actual);
+  assert_eq!(detected.model, None);
+}
+
+#[test]
+fn fails_when_nothing_answers() {
+  let mut attempts = 0;
+  let result = detect::detect_with("mock", &ProtocolOptions::default(), |_| {
+    attempts += 1;
+    Ok(Line::Garbled)
+  });
+
+  assert!(matches!(result, Err(Error::DetectionFailed { ref path }) if path == "mock"));
+  // every rate with each of the four line formats
+  assert_eq!(attempts, detect::BAUD_RATES.len() * 4);
+
+  // open errors abort detection straight away
+  let result = detect::detect_with::<Line, _>("mock", &ProtocolOptions::default(), |_| {
+    Err(Error::Disconnected)
+  });
+  assert!(matches!(result, Err(Error::Disconnected)));
+}
@@ -0,0 +1,84 @@
+use benq_control::Error;
+use benq_control::discovery::{PortSelector, UsbFilter};
+use serialport::UsbPortInfo;
+
+fn usb(vid: Option<u16>, pid: Option<u16>, serial_number: Option<&str>) -> PortSelector {
+  PortSelector::Usb(UsbFilter {
+    vid,
+    pid,
+    serial_number: serial_number.map(String::from),
+  })
+}
+
+#[test]
+fn parses_selectors() {
+  let cases = vec![
+    ("/dev/ttyUSB0", PortSelector::Path("/dev/ttyUSB0".to_string())),
+    ("COM3", PortSelector::Path("COM3".to_string())),
+    ("usbserial", PortSelector::Path("usbserial".to_string())),
+    ("auto", PortSelector::Auto),
+    ("AUTO", PortSelector::Auto),
+    ("usb", usb(None, None, None)),
+    ("usb:", usb(None, None, None)),
+    ("usb:0403", usb(Some(0x0403), None, None)),
+    ("usb:0403:6001", usb(Some(0x0403), Some(0x6001), None)),
+    ("usb:0403:6001:A10K", usb(Some(0x0403), Some(0x6001), Some("A10K"))),
+    ("usb::6001", usb(None, Some(0x6001), None)),
+    ("usb:::A10K", usb(None, None, Some("A10K"))),
+    ("usb:0403::A1:0K", usb(Some(0x0403), None, Some("A1:0K"))),
+  ];
+
+  for (s, expected) in cases {
+    assert_eq!(s.parse::<PortSelector>().unwrap(), expected, "{}", s);
+  }
+
+  for s in &["usb:xyz", "usb:0403:12345", "usb:-1"] {
+    let result = s.parse::<PortSelector>();
+    assert!(matches!(result, Err(Error::InvalidPortSelector(_))), "{}: {:?}", s, result);
+  }
+}
+
+#[test]
+fn display_round_trips() {
+  let selectors = vec![
+    PortSelector::Path("/dev/ttyUSB0".to_string()),
+    PortSelector::Auto,
+    usb(None, None, None),
+    usb(Some(0x0403), None, None),
+    usb(Some(0x0403), Some(0x6001), None),
+    usb(Some(0x0403), Some(0x6001), Some("A10K")),
+    usb(None, Some(0x6001), None),
+    usb(None, None, Some("A10K")),
+  ];
+
+  for selector in selectors {
+    let s = selector.to_string();
+    assert_eq!(s.parse::<PortSelector>().unwrap(), selector, "{}", s);
+  }
+
+  assert_eq!(usb(None, None, None).to_string(), "usb");
+  assert_eq!(usb(Some(0x0403), Some(0x6001), None).to_string(), "usb:0403:6001");
+  assert_eq!(usb(None, None, Some("A10K")).to_string(), "usb:::A10K");
+}
+
+#[test]
+fn filters_match_unset_fields() {
+  let info = UsbPortInfo {
+    vid: 0x0403,
+    pid: 0x6001,
+    serial_number: Some("A10K".to_string()),
+    manufacturer: None,
+    product: None,
+  };
+
+  let filter = |s: &str| match s.parse::<PortSelector>().unwrap() {
+    PortSelector::Usb(filter) => filter,
+    other => panic!("not a usb selector: {:?}", other),
+  };
+
+  assert!(filter("usb").matches(&info));
+  assert!(filter("usb:0403").matches(&info));
+  assert!(filter("usb::6001:A10K").matches(&info));
+  assert!(!filter("usb:0404").matches(&info));
+  assert!(!filter("usb:0403:6001:B20K").matches(&info));
+}
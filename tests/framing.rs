@@ -0,0 +1,333 @@
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+
+use benq_control::{Error, Result};
+use benq_control::protocol::{
+  self, EchoMode, LineEnding, PromptMode, ProtocolOptions, Transport
+};
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use serialport::ClearBuffer;
+
+/// A fake projector that sprinkles noise around everything it sends.
+struct NoisyProjector {
+  rng: StdRng,
+
+  /// Probability of injecting noise at any given point
+  noise: f64,
+
+  /// Probability of truncating the echo
+  partial_echo: f64,
+
+  /// Whether commands are echoed back
+  echo: bool,
+
+  /// Whether a prompt is printed on wake
+  prompt: bool,
+
+  /// Whether noise may contain frame delimiters and prompt characters
+  delimiters: bool,
+
+  /// The response frame body for the next command
+  response: String,
+
+  written: Vec<u8>,
+  pending: VecDeque<u8>,
+}
+
+impl NoisyProjector {
+  fn new(seed: u64, noise: f64, partial_echo: f64) -> Self {
+    NoisyProjector {
+      rng: StdRng::seed_from_u64(seed),
+      noise,
+      partial_echo,
+      echo: true,
+      prompt: true,
+      delimiters: false,
+      response: String::new(),
+      written: Vec::new(),
+      pending: VecDeque::new(),
+    }
+  }
+
+  /// Maybe pushes some garbage. Unless `delimiters` is set, it never
+  /// contains frame delimiters or the prompt character; if it is, delimiters
+  /// are more likely than other bytes, but a burst never contains a `*`
+  /// followed by a `#` as that would be a genuine frame.
+  fn maybe_noise(&mut self) {
+    if !self.rng.gen_bool(self.noise) {
+      return;
+    }
+
+    let len = self.rng.gen_range(1..16);
+    let mut frame_started = false;
+    for _ in 0..len {
+      let b = loop {
+        let b: u8 = if self.delimiters && self.rng.gen_bool(0.3) {
+          b"*#>"[self.rng.gen_range(0..3)]
+        } else {
+          self.rng.gen()
+        };
+
+        let allowed = match b {
+          b'*' | b'>' => self.delimiters,
+          b'#' => self.delimiters && !frame_started,
+          _ => true
+        };
+        if allowed {
+          break b;
+        }
+      };
+
+      frame_started |= b == b'*';
+      self.pending.push_back(b);
+    }
+  }
+
+  fn push(&mut self, bytes: &[u8]) {
+    self.pending.extend(bytes);
+  }
+
+  fn handle_line(&mut self, mut line: Vec<u8>) {
+    // leftover from a \r\n line ending
+    if line.starts_with(b"\n") {
+      line.remove(0);
+    }
+
+    if line == b"\r" {
+      self.maybe_noise();
+      if self.prompt {
+        self.push(b">");
+      }
+
+      return;
+    }
+
+    let echo = &line[..line.len() - 1];
+    let echo = if !self.echo {
+      Vec::new()
+    } else if self.rng.gen_bool(self.partial_echo) {
+      let cut = self.rng.gen_range(1..echo.len());
+      if self.rng.gen() {
+        echo[cut..].to_vec()
+      } else {
+        echo[..cut].to_vec()
+      }
+    } else {
+      echo.to_vec()
+    };
+
+    self.maybe_noise();
+    self.push(&echo);
+    self.maybe_noise();
+    self.push(b"\r\n");
+    self.maybe_noise();
+
+    let response = format!("*{}#", self.response);
+    self.push(response.as_bytes());
+    self.maybe_noise();
+  }
+}
+
+impl Read for NoisyProjector {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    if self.pending.is_empty() {
+      return Err(io::ErrorKind::TimedOut.into());
+    }
+
+    // deliver in arbitrary chunks
+    let n = self.rng.gen_range(1..=buf.len().min(self.pending.len()));
+    for b in buf.iter_mut().take(n) {
+      *b = self.pending.pop_front().unwrap();
+    }
+
+    Ok(n)
+  }
+}
+
+impl Write for NoisyProjector {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    for &b in buf {
+      self.written.push(b);
+      if b == b'\r' {
+        let line = std::mem::take(&mut self.written);
+        self.handle_line(line);
+      }
+    }
+
+    Ok(buf.len())
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    Ok(())
+  }
+}
+
+impl Transport for NoisyProjector {
+  fn clear(&mut self, buffer: ClearBuffer) -> Result<()> {
+    if let ClearBuffer::Input | ClearBuffer::All = buffer {
+      self.pending.clear();
+    }
+
+    // line noise may arrive right after clearing
+    self.maybe_noise();
+    Ok(())
+  }
+}
+
+#[test]
+fn get_and_set_survive_noise() {
+  for seed in 0..100 {
+    let mut port = NoisyProjector::new(seed, 0.5, 0.1);
+
+    port.response = "POW=ON".to_string();
+    let res = protocol::send_get(&mut port, "pow", &ProtocolOptions::default());
+    assert_eq!(res.unwrap().as_deref(), Some("POW=ON"), "seed {}", seed);
+
+    port.response = "SOUR=HDMI2".to_string();
+    let res = protocol::send_set(&mut port, "sour", "hdmi2", &ProtocolOptions::default());
+    assert_eq!(res.unwrap().as_deref(), Some("SOUR=HDMI2"), "seed {}", seed);
+  }
+}
+
+#[test]
+fn survives_delimiters_in_noise() {
+  // a truncated echo can't be told apart from a stray frame, so echoes are
+  // always complete here
+  for seed in 0..100 {
+    let mut port = NoisyProjector::new(seed, 0.5, 0.0);
+    port.delimiters = true;
+
+    port.response = "POW=ON".to_string();
+    let res = protocol::send_get(&mut port, "pow", &ProtocolOptions::default());
+    assert_eq!(res.unwrap().as_deref(), Some("POW=ON"), "seed {}", seed);
+
+    port.response = "Block item".to_string();
+    let res = protocol::send_set(&mut port, "sour", "hdmi2", &ProtocolOptions::default());
+    assert!(matches!(res, Err(Error::ResponseBlockItem)), "seed {}: {:?}", seed, res);
+  }
+}
+
+#[test]
+fn echoless_firmware() {
+  let modes = [EchoMode::Auto, EchoMode::Never];
+
+  for (seed, mode) in modes.iter().cycle().take(20).enumerate() {
+    let options = ProtocolOptions {
+      echo: *mode,
+      line_ending: LineEnding::CrLf,
+      prompt: PromptMode::Optional,
+    };
+
+    let mut port = NoisyProjector::new(seed as u64, 0.5, 0.0);
+    port.echo = false;
+    port.prompt = seed % 3 != 0;
+
+    port.response = "POW=OFF".to_string();
+    let res = protocol::send_get(&mut port, "pow", &options);
+    assert_eq!(res.unwrap().as_deref(), Some("POW=OFF"), "seed {}", seed);
+  }
+}
+
+#[test]
+fn echo_modes() {
+  let echo = b"*pow=on#";
+
+  // an echoless projector answering with the same text as the command
+  let res = protocol::finish_response(b"*pow=on#", echo, EchoMode::Never);
+  assert_eq!(res.unwrap().as_deref(), Some("pow=on"));
+
+  let res = protocol::finish_response(b"*pow=on#", echo, EchoMode::Auto);
+  assert_eq!(res.unwrap(), None);
+
+  let res = protocol::finish_response(b"*POW=ON#", echo, EchoMode::Always);
+  assert!(matches!(res, Err(Error::ResponseUnexpectedFormat(_))), "{:?}", res);
+
+  let res = protocol::finish_response(b"", echo, EchoMode::Auto);
+  assert_eq!(res.unwrap(), None);
+}
+
+#[test]
+fn block_item_survives_noise() {
+  for seed in 0..50 {
+    let mut port = NoisyProjector::new(seed, 0.5, 0.1);
+    port.response = "Block item".to_string();
+
+    let res = protocol::send_get(&mut port, "vol", &ProtocolOptions::default());
+    assert!(matches!(res, Err(Error::ResponseBlockItem)), "seed {}: {:?}", seed, res);
+  }
+}
+
+#[test]
+fn resynchronises_on_frame_start() {
+  let echo = b"*pow=?#";
+
+  // stale partial frame before the echo
+  let res = protocol::finish_response(b"*POW=O\x00*pow=?#\r\n*POW=OFF#", echo, EchoMode::Auto);
+  assert_eq!(res.unwrap().as_deref(), Some("POW=OFF"));
+
+  // truncated echo running into the response
+  let res = protocol::finish_response(b"*pow=\xff\xfe*POW=ON#\r\n>", echo, EchoMode::Auto);
+  assert_eq!(res.unwrap().as_deref(), Some("POW=ON"));
+
+  // frame abandoned at a line break
+  let res = protocol::finish_response(b"*pow=?#\r\n*VOL\r\n*VOL=5#", echo, EchoMode::Auto);
+  assert_eq!(res.unwrap().as_deref(), Some("VOL=5"));
+
+  // echo followed by nothing but a prompt
+  let res = protocol::finish_response(b"\x13garbage*pow=?#\r\n>", echo, EchoMode::Auto);
+  assert_eq!(res.unwrap(), None);
+}
+
+#[test]
+fn resynchronises_on_stray_delimiters() {
+  let echo = b"*pow=?#";
+
+  for mode in [EchoMode::Auto, EchoMode::Always].iter() {
+    // stray prompt before the echo
+    let res = protocol::finish_response(b">\x00>*pow=?#\r\n*POW=ON#", echo, *mode);
+    assert_eq!(res.unwrap().as_deref(), Some("POW=ON"), "{:?}", mode);
+
+    // stray frame ends in noise on either side of the echo
+    let res = protocol::finish_response(b"ab#c*pow=?#\r\n#x#*POW=ON#", echo, *mode);
+    assert_eq!(res.unwrap().as_deref(), Some("POW=ON"), "{:?}", mode);
+
+    // half a frame followed by the real response
+    let res = protocol::finish_response(b"*pow=?#\r\n*POW=*POW=OFF#", echo, *mode);
+    assert_eq!(res.unwrap().as_deref(), Some("POW=OFF"), "{:?}", mode);
+
+    // half a frame running into the echo
+    let res = protocol::finish_response(b"*POW=*pow=?#\r\n*POW=ON#\r\n>", echo, *mode);
+    assert_eq!(res.unwrap().as_deref(), Some("POW=ON"), "{:?}", mode);
+  }
+}
+
+#[test]
+fn missing_prompt_is_invalid_state() {
+  struct Silent;
+
+  impl Read for Silent {
+    fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+      Err(io::ErrorKind::TimedOut.into())
+    }
+  }
+
+  impl Write for Silent {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+      Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+      Ok(())
+    }
+  }
+
+  impl Transport for Silent {
+    fn clear(&mut self, _buffer: ClearBuffer) -> Result<()> {
+      Ok(())
+    }
+  }
+
+  let res = protocol::send_get(&mut Silent, "pow", &ProtocolOptions::default());
+  assert!(matches!(res, Err(Error::CommandSendInvalidState)), "{:?}", res);
+}
@@ -0,0 +1,85 @@
+use benq_control::{Error, Pacing, PowerState, ProjectorControl, Source};
+use benq_control::group::ProjectorGroup;
+use benq_control::testing::{MockHandle, MockProjector, Reply};
+use futures::executor::block_on;
+
+fn group(left: MockProjector, right: MockProjector) -> (ProjectorGroup, MockHandle, MockHandle) {
+  let (left_handle, right_handle) = (left.handle(), right.handle());
+  let control = |mock| ProjectorControl::builder().pacing(Pacing::none()).build(mock);
+
+  let group = ProjectorGroup::new()
+    .with_member("left", control(left))
+    .with_member("right", control(right));
+
+  (group, left_handle, right_handle)
+}
+
+#[test]
+fn fans_out_commands() {
+  let (group, left, right) = group(
+    MockProjector::new().expect(("pow", "on"), Reply::response("POW=ON")),
+    MockProjector::new().expect(("pow", "on"), Reply::response("POW=ON")),
+  );
+
+  block_on(group.set_power(PowerState::On)).unwrap();
+
+  left.assert_done();
+  right.assert_done();
+}
+
+#[test]
+fn reports_partial_failures() {
+  let (group, left, right) = group(
+    MockProjector::new().expect(("sour", "hdmi"), Reply::response("SOUR=HDMI")),
+    MockProjector::new().expect(("sour", "hdmi"), Reply::BlockItem),
+  );
+
+  let results = block_on(group.submit_command(("sour", "hdmi")));
+  assert!(!results.is_ok());
+  assert_eq!(results.failures().map(|(name, _)| name).collect::<Vec<_>>(), vec!["right"]);
+
+  match results.into_result() {
+    Err(Error::GroupFailed { total, failed }) => {
+      assert_eq!(total, 2);
+      assert_eq!(failed.len(), 1);
+      assert_eq!(failed[0].0, "right");
+      assert!(matches!(failed[0].1, Error::ResponseBlockItem));
+    },
+    other => panic!("unexpected result {:?}", other),
+  }
+
+  left.assert_done();
+  right.assert_done();
+}
+
+#[test]
+fn queries_must_agree() {
+  let (group, left, right) = group(
+    MockProjector::new()
+      .expect("pow", Reply::response("POW=ON"))
+      .expect("sour", Reply::response("SOUR=HDMI")),
+    MockProjector::new()
+      .expect("pow", Reply::response("POW=ON"))
+      .expect("sour", Reply::response("SOUR=HDMI2")),
+  );
+
+  assert_eq!(block_on(group.power()).unwrap(), PowerState::On);
+
+  let err = block_on(group.source()).unwrap_err();
+  assert_eq!(err.to_string(), "projectors disagree: left: hdmi, right: hdmi2");
+  match err {
+    Error::GroupDisagrees { values } => assert_eq!(values, vec![
+      ("left".to_string(), Source::Hdmi.to_string()),
+      ("right".to_string(), Source::Hdmi2.to_string()),
+    ]),
+    other => panic!("unexpected error {:?}", other),
+  }
+
+  left.assert_done();
+  right.assert_done();
+}
+
+#[test]
+fn empty_groups_cannot_agree() {
+  assert!(matches!(block_on(ProjectorGroup::new().power()), Err(Error::GroupEmpty)));
+}
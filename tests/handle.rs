@@ -0,0 +1,83 @@
+use benq_control::{Command, Error, Pacing, ProjectorControl};
+use benq_control::testing::{MockProjector, Reply};
+use futures::executor::block_on;
+
+mod common;
+use common::{wait_until, Gate};
+
+fn start(mock: MockProjector, gate: &Gate) -> ProjectorControl {
+  ProjectorControl::builder()
+    .pacing(Pacing::none())
+    .build(gate.wrap(mock))
+}
+
+#[test]
+fn clones_share_the_worker() {
+  let mock = MockProjector::new()
+    .expect("pow", Reply::response("POW=ON"))
+    .expect("vol", Reply::response("VOL=5"));
+  let handle = mock.handle();
+  let gate = Gate::default();
+  gate.open();
+
+  let first = start(mock, &gate);
+  let second = first.clone();
+  assert_eq!(block_on(first.submit_command("pow")).unwrap().as_deref(), Some("POW=ON"));
+
+  drop(first);
+  assert_eq!(block_on(second.submit_command("vol")).unwrap().as_deref(), Some("VOL=5"));
+  assert!(!gate.is_dropped());
+
+  handle.assert_done();
+}
+
+#[test]
+fn dropping_the_last_handle_stops_the_worker() {
+  let mock = MockProjector::new()
+    .expect("pow", Reply::response("POW=ON"))
+    .expect("mute", Reply::response("MUTE=OFF"));
+  let handle = mock.handle();
+  let gate = Gate::default();
+
+  let projector = start(mock, &gate);
+  let pow = projector.submit_command("pow");
+  wait_until(|| projector.queue_status().current.is_some());
+
+  // nobody waits for vol, so it's dropped rather than sent
+  drop(projector.submit_command("vol"));
+  let mute = projector.submit_command("mute");
+  drop(projector);
+
+  gate.open();
+  assert_eq!(block_on(pow).unwrap().as_deref(), Some("POW=ON"));
+  assert_eq!(block_on(mute).unwrap().as_deref(), Some("MUTE=OFF"));
+
+  wait_until(|| gate.is_dropped());
+  handle.assert_done();
+  assert_eq!(handle.commands(), vec![Command::from("pow"), Command::from("mute")]);
+}
+
+#[test]
+fn shutdown_waits_for_the_worker() {
+  let mock = MockProjector::new()
+    .expect("pow", Reply::response("POW=ON"));
+  let handle = mock.handle();
+  let gate = Gate::default();
+  gate.open();
+
+  let projector = start(mock, &gate);
+  let other = projector.clone();
+  let pow = projector.submit_command("pow");
+
+  // queued commands run first, even with other handles still around
+  block_on(projector.shutdown());
+  assert!(gate.is_dropped());
+  assert_eq!(block_on(pow).unwrap().as_deref(), Some("POW=ON"));
+
+  let result = block_on(other.submit_command("vol"));
+  assert!(matches!(result, Err(Error::CommandSendError { .. })), "{:?}", result);
+
+  // shutting down again is harmless
+  block_on(other.shutdown());
+  handle.assert_done();
+}
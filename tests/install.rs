@@ -0,0 +1,72 @@
+use benq_control::{
+  Error, KeystoneAxis, Language, ModelProfile, Pacing, ProjectorControl, ProjectorPosition
+};
+use benq_control::testing::{MockProjector, Reply};
+
+fn control(mock: MockProjector) -> benq_control::blocking::ProjectorControl {
+  ProjectorControl::builder()
+    .pacing(Pacing::none())
+    .build_blocking(mock)
+}
+
+#[test]
+fn sets_position_and_language() {
+  let mock = MockProjector::new()
+    .expect(("pp", "fc"), Reply::response("PP=FC"))
+    .expect("pp", Reply::response("PP=FC"))
+    .expect(("lang", "german"), Reply::response("LANG=GERMAN"))
+    .expect("lang", Reply::response("LANG=GERMAN"));
+  let handle = mock.handle();
+
+  let projector = control(mock);
+  projector.set_position(&ProjectorPosition::FrontCeiling).unwrap();
+  assert_eq!(projector.position().unwrap(), ProjectorPosition::FrontCeiling);
+  projector.set_language(&Language::German).unwrap();
+  assert_eq!(projector.language().unwrap(), Language::German);
+
+  handle.assert_done();
+}
+
+#[test]
+fn adjusts_keystone() {
+  let mock = MockProjector::new()
+    .expect(("vkeystone", "-5"), Reply::response("VKEYSTONE=-5"))
+    .expect(("vkeystone", "+"), Reply::response("VKEYSTONE=+"))
+    .expect("vkeystone", Reply::response("VKEYSTONE=-4"))
+    .expect(("hkeystone", "-"), Reply::response("HKEYSTONE=-"));
+  let handle = mock.handle();
+
+  let projector = control(mock);
+  projector.set_keystone(KeystoneAxis::Vertical, -5).unwrap();
+  projector.keystone_up(KeystoneAxis::Vertical).unwrap();
+  assert_eq!(projector.keystone(KeystoneAxis::Vertical).unwrap(), -4);
+  projector.keystone_down(KeystoneAxis::Horizontal).unwrap();
+
+  handle.assert_done();
+}
+
+#[test]
+fn validates_against_profile() {
+  let mock = MockProjector::new();
+  let handle = mock.handle();
+
+  let projector = control(mock);
+  projector.set_profile(ModelProfile::th685());
+
+  assert!(matches!(
+    projector.set_keystone(KeystoneAxis::Vertical, 40),
+    Err(Error::Unsupported { .. })
+  ));
+  assert!(matches!(
+    projector.set_keystone(KeystoneAxis::Horizontal, 0),
+    Err(Error::Unsupported { .. })
+  ));
+
+  projector.set_profile(ModelProfile {
+    languages: Some(vec![Language::English]),
+    ..ModelProfile::default()
+  });
+  assert!(matches!(projector.set_language(&Language::French), Err(Error::Unsupported { .. })));
+
+  handle.assert_done();
+}
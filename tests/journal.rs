@@ -0,0 +1,60 @@
+use benq_control::{Pacing, PowerState, ProjectorControl};
+use benq_control::testing::{MockProjector, Reply};
+
+#[test]
+fn records_the_bytes_on_the_wire() {
+  let mock = MockProjector::new()
+    .expect("pow", Reply::response("POW=ON"))
+    .expect("vol", Reply::BlockItem);
+
+  let projector = ProjectorControl::builder()
+    .pacing(Pacing::none())
+    .build_blocking(mock);
+  assert_eq!(projector.power().unwrap(), PowerState::On);
+  assert!(projector.volume().is_err());
+
+  let journal = projector.journal();
+  assert_eq!(journal.len(), 2);
+
+  assert_eq!(journal[0].command, "pow".into());
+  assert_eq!(journal[0].written, b"\r*pow=?#\r");
+  assert_eq!(journal[0].read, b">*pow=?#\r\n*POW=ON#\r\n");
+  assert_eq!(journal[0].result, Ok(Some("POW=ON".to_string())));
+
+  assert_eq!(journal[1].command, "vol".into());
+  assert_eq!(journal[1].read, b">*vol=?#\r\n*Block item#\r\n");
+  assert!(journal[1].result.is_err());
+}
+
+#[test]
+fn keeps_only_the_latest_entries() {
+  let mock = MockProjector::new()
+    .expect("pow", Reply::response("POW=ON"))
+    .expect("sour", Reply::response("SOUR=HDMI"))
+    .expect("vol", Reply::response("VOL=5"));
+
+  let projector = ProjectorControl::builder()
+    .pacing(Pacing::none())
+    .journal(2)
+    .build_blocking(mock);
+  projector.power().unwrap();
+  projector.source().unwrap();
+  projector.volume().unwrap();
+
+  let commands: Vec<_> = projector.journal().into_iter().map(|e| e.command).collect();
+  assert_eq!(commands, vec!["sour".into(), "vol".into()]);
+}
+
+#[test]
+fn can_be_disabled() {
+  let mock = MockProjector::new()
+    .expect("pow", Reply::response("POW=ON"));
+
+  let projector = ProjectorControl::builder()
+    .pacing(Pacing::none())
+    .journal(0)
+    .build_blocking(mock);
+  projector.power().unwrap();
+
+  assert!(projector.journal().is_empty());
+}
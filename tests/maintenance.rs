@@ -0,0 +1,63 @@
+use benq_control::{Error, LampMode, MaintenanceStatus, ModelProfile, Pacing, ProjectorControl};
+use benq_control::testing::{MockProjector, Reply};
+
+fn control(mock: MockProjector) -> benq_control::blocking::ProjectorControl {
+  ProjectorControl::builder()
+    .pacing(Pacing::none())
+    .build_blocking(mock)
+}
+
+#[test]
+fn reports_lamp_status() {
+  let mock = MockProjector::new()
+    .expect("ltim", Reply::response("LTIM=1234"))
+    .expect("lampm", Reply::response("LAMPM=SECO"));
+  let handle = mock.handle();
+
+  let projector = control(mock);
+  assert_eq!(projector.lamp_hours().unwrap(), 1234);
+  assert_eq!(projector.lamp_mode().unwrap(), LampMode::SmartEco);
+
+  handle.assert_done();
+}
+
+#[test]
+fn reports_every_counter_in_profile() {
+  let mock = MockProjector::new()
+    .expect("ltim", Reply::response("LTIM=1200"))
+    .expect("ltim2", Reply::response("LTIM2=800"))
+    .expect("filter", Reply::response("FILTER=90"))
+    .expect("lampm", Reply::response("LAMPM=LNOR"));
+  let handle = mock.handle();
+
+  let projector = control(mock);
+  projector.set_profile(ModelProfile {
+    lamp_timers: vec!["ltim".into(), "ltim2".into()],
+    filter_timer: Some("filter".into()),
+    ..ModelProfile::default()
+  });
+
+  assert_eq!(projector.maintenance().unwrap(), MaintenanceStatus {
+    lamp_mode: LampMode::Normal,
+    lamp_hours: vec![1200, 800],
+    filter_hours: Some(90),
+  });
+
+  handle.assert_done();
+}
+
+#[test]
+fn validates_lamp_mode() {
+  let mock = MockProjector::new()
+    .expect(("lampm", "eco"), Reply::response("LAMPM=ECO"));
+  let handle = mock.handle();
+
+  let projector = control(mock);
+  projector.set_profile(ModelProfile::th685());
+
+  let unsupported = LampMode::Other("dual".into());
+  assert!(matches!(projector.set_lamp_mode(&unsupported), Err(Error::Unsupported { .. })));
+  projector.set_lamp_mode(&LampMode::Eco).unwrap();
+
+  handle.assert_done();
+}
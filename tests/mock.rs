@@ -0,0 +1,116 @@
+use std::time::Duration;
+
+use benq_control::{Command, Error, Pacing, PowerState, ProjectorControl, Source};
+use benq_control::testing::{MockProjector, Reply};
+
+fn control(mock: MockProjector) -> benq_control::blocking::ProjectorControl {
+  ProjectorControl::builder()
+    .pacing(Pacing::none())
+    .build_blocking(mock)
+}
+
+#[test]
+fn answers_expected_commands() {
+  let mock = MockProjector::new()
+    .expect("pow", Reply::response("POW=ON"))
+    .expect("sour", Reply::response("SOUR=HDMI2"))
+    .expect(("sour", "hdmi"), Reply::response("SOUR=HDMI"));
+  let handle = mock.handle();
+
+  let projector = control(mock);
+  assert_eq!(projector.power().unwrap(), PowerState::On);
+  assert_eq!(projector.source().unwrap(), Source::Hdmi2);
+  projector.set_source(&Source::Hdmi).unwrap();
+
+  handle.assert_done();
+  assert_eq!(handle.commands(), vec![
+    Command::from("pow"),
+    Command::from("sour"),
+    Command::from(("sour", "hdmi")),
+  ]);
+}
+
+#[test]
+fn works_without_echo() {
+  let mock = MockProjector::new()
+    .echo(false)
+    .expect("vol", Reply::response("VOL=7"));
+  let handle = mock.handle();
+
+  assert_eq!(control(mock).volume().unwrap(), 7);
+  handle.assert_done();
+}
+
+#[test]
+fn injects_errors() {
+  let mock = MockProjector::new()
+    .expect("pow", Reply::BlockItem)
+    .expect("pow", Reply::NoPrompt)
+    .expect("pow", Reply::Silence)
+    .expect("pow", Reply::response("POW=OFF"));
+  let handle = mock.handle();
+
+  let projector = control(mock);
+  assert!(matches!(projector.power(), Err(Error::ResponseBlockItem)));
+  assert!(matches!(projector.power(), Err(Error::CommandSendInvalidState)));
+  assert!(projector.power().is_err());
+  assert_eq!(projector.power().unwrap(), PowerState::Off);
+
+  handle.assert_done();
+}
+
+#[test]
+fn refuses_unexpected_commands() {
+  let mock = MockProjector::new()
+    .expect("pow", Reply::response("POW=ON"));
+  let handle = mock.handle();
+
+  let projector = control(mock);
+  assert!(matches!(projector.muted(), Err(Error::ResponseBlockItem)));
+  assert_eq!(projector.power().unwrap(), PowerState::On);
+
+  let result = std::panic::catch_unwind(|| handle.assert_done());
+  assert!(result.is_err());
+}
+
+#[test]
+#[should_panic(expected = "never received")]
+fn reports_missing_commands() {
+  let mock = MockProjector::new()
+    .expect("pow", Reply::response("POW=ON"))
+    .expect("sour", Reply::response("SOUR=HDMI"));
+  let handle = mock.handle();
+
+  control(mock).power().unwrap();
+  handle.assert_done();
+}
+
+#[test]
+fn rejects_unsafe_commands() {
+  let mock = MockProjector::new();
+  let handle = mock.handle();
+
+  let projector = control(mock);
+  let injected = Command::from(("pow", "on#\r*pow=off"));
+  assert!(matches!(projector.submit_command(injected), Err(Error::InvalidCommand(_))));
+  assert!(matches!(projector.submit_command("pow#"), Err(Error::InvalidCommand(_))));
+
+  assert!(handle.commands().is_empty());
+}
+
+#[test]
+fn times_out_waiting_for_a_response() {
+  let mock = MockProjector::new()
+    .expect("pow", Reply::Silence)
+    .expect("pow", Reply::response("POW=ON"));
+  let handle = mock.handle();
+
+  let mut projector = control(mock);
+  projector.set_timeout(Some(Duration::from_millis(50)));
+  assert!(matches!(projector.power(), Err(Error::Timeout)));
+
+  // the silent command is still in flight, so wait it out
+  projector.set_timeout(None);
+  assert_eq!(projector.power().unwrap(), PowerState::On);
+  handle.assert_done();
+}
@@ -0,0 +1,173 @@
+//! Property tests for response parsing: whatever the projector sends, parsing
+//! must not panic, and must never mistake garbage for a response.
+
+use benq_control::{Command, Error};
+use benq_control::protocol::{self, EchoMode};
+use proptest::prelude::*;
+
+const MODES: [EchoMode; 3] = [EchoMode::Auto, EchoMode::Always, EchoMode::Never];
+
+/// Bytes that never contain frame delimiters, line breaks or the prompt.
+fn noise() -> impl Strategy<Value = Vec<u8>> {
+  prop::collection::vec(
+    any::<u8>().prop_filter("delimiter", |b| !b"*#>\r\n".contains(b)),
+    0..16
+  )
+}
+
+/// A response frame body, possibly containing multi-byte characters.
+fn body() -> impl Strategy<Value = String> {
+  "[^*#\r\n]{1,16}".prop_filter("block item", |b| !b.eq_ignore_ascii_case("block item"))
+}
+
+fn key() -> impl Strategy<Value = String> {
+  "[a-z0-9]{1,8}"
+}
+
+/// Builds what a well-behaved projector sends back for `echo`: some noise, the
+/// echo and the response frame.
+fn response(noise: &[u8], echo: &[u8], body: &str) -> Vec<u8> {
+  let mut buf = noise.to_vec();
+  buf.extend_from_slice(echo);
+  buf.extend_from_slice(b"\r\n*");
+  buf.extend_from_slice(body.as_bytes());
+  buf.extend_from_slice(b"#\r\n");
+  buf
+}
+
+proptest! {
+  #[test]
+  fn parsing_arbitrary_bytes_never_panics(
+    buf in prop::collection::vec(any::<u8>(), 0..128),
+    echo in prop::collection::vec(any::<u8>(), 0..16)
+  ) {
+    for mode in MODES {
+      let _ = protocol::parse_response(&buf, &echo, mode);
+      let _ = protocol::finish_response(&buf, &echo, mode);
+    }
+
+    let _ = protocol::find_prompt(&buf);
+  }
+
+  #[test]
+  fn parsing_frame_like_bytes_never_panics(
+    buf in prop::collection::vec(prop::sample::select(b"*#>\r\n=?aZ\xff\xc3\xa9".to_vec()), 0..64),
+    key in key()
+  ) {
+    let echo = protocol::encode_frame(&format!("{}=?", key));
+    for mode in MODES {
+      let _ = protocol::parse_response(&buf, &echo, mode);
+      let _ = protocol::finish_response(&buf, &echo, mode);
+    }
+  }
+
+  #[test]
+  fn frames_are_well_formed(buf in prop::collection::vec(any::<u8>(), 0..128)) {
+    let mut last_end = 0;
+    for frame in protocol::frames(&buf) {
+      prop_assert!(frame.start >= last_end);
+      prop_assert_eq!(buf[frame.start], b'*');
+      prop_assert_eq!(buf[frame.end - 1], b'#');
+      prop_assert_eq!(frame.body, &buf[frame.start + 1..frame.end - 1]);
+      prop_assert!(!frame.body.iter().any(|b| b"*\r\n".contains(b)));
+      last_end = frame.end;
+    }
+  }
+
+  #[test]
+  fn parses_responses(noise in noise(), key in key(), body in body()) {
+    let echo = protocol::encode_frame(&format!("{}=?", key));
+    prop_assume!(protocol::encode_frame(&body) != echo);
+
+    let buf = response(&noise, &echo, &body);
+    for mode in [EchoMode::Auto, EchoMode::Always] {
+      let parsed = protocol::finish_response(&buf, &echo, mode);
+      prop_assert_eq!(parsed.ok(), Some(Some(body.clone())));
+    }
+  }
+
+  #[test]
+  fn truncated_responses_are_incomplete(
+    noise in noise(),
+    key in key(),
+    body in body(),
+    cut in any::<prop::sample::Index>()
+  ) {
+    let echo = protocol::encode_frame(&format!("{}=?", key));
+    prop_assume!(protocol::encode_frame(&body) != echo);
+
+    let buf = response(&noise, &echo, &body);
+    let truncated = &buf[..cut.index(buf.len())];
+
+    // either nothing yet, or the real response if only the line ending is cut
+    for mode in [EchoMode::Auto, EchoMode::Always] {
+      match protocol::parse_response(truncated, &echo, mode) {
+        None => (),
+        Some(Ok(Some(parsed))) => prop_assert_eq!(parsed, body.clone()),
+        Some(other) => prop_assert!(false, "unexpected result {:?}", other),
+      }
+    }
+  }
+
+  #[test]
+  fn rejects_invalid_utf8(
+    key in key(),
+    invalid in prop::sample::select(vec![&b"\xff"[..], b"\xc3", b"\xe2\x82", b"\xed\xa0\x80"])
+  ) {
+    let echo = protocol::encode_frame(&format!("{}=?", key));
+    let mut buf = echo.clone();
+    buf.extend_from_slice(b"\r\n*POW=");
+    buf.extend_from_slice(invalid);
+    buf.extend_from_slice(b"#\r\n");
+
+    let parsed = protocol::finish_response(&buf, &echo, EchoMode::Always);
+    let invalid_string = matches!(parsed, Err(Error::ResponseInvalidString { .. }));
+    prop_assert!(invalid_string, "unexpected result {:?}", parsed);
+  }
+
+  #[test]
+  fn commands_round_trip(key in key(), value in "[A-Za-z0-9.+-]{1,8}") {
+    let get = Command::Get(key.clone());
+    prop_assert_eq!(get.to_string().parse::<Command>().unwrap(), get);
+
+    let action = Command::Action(key.clone());
+    prop_assert_eq!(action.to_string().parse::<Command>().unwrap(), action);
+
+    let set = Command::Set((key, value));
+    prop_assert_eq!(set.to_string().parse::<Command>().unwrap(), set);
+  }
+
+  #[test]
+  fn parsed_commands_round_trip(s in any::<String>()) {
+    if let Ok(command) = s.parse::<Command>() {
+      prop_assert_eq!(command.to_string().parse::<Command>().unwrap(), command);
+    }
+  }
+
+  #[test]
+  fn parsed_commands_cannot_inject_frames(s in "[ -~\r\n]{0,16}") {
+    if let Ok(command) = s.parse::<Command>() {
+      let frame = protocol::encode_frame(&command.to_string());
+      prop_assert_eq!(protocol::frames(&frame).count(), 1);
+      prop_assert!(!frame.iter().any(|b| b"\r\n>".contains(b)));
+    }
+  }
+
+  #[test]
+  fn rejects_unsafe_commands(
+    key in key(),
+    unsafe_char in prop::sample::select(vec!['*', '#', '=', '?', '\r', '\n', ' ', '\u{e9}'])
+  ) {
+    let value = format!("on{}pow", unsafe_char);
+    let command = Command::from((key.as_str(), value.as_str()));
+    let invalid = matches!(command.validate(), Err(Error::InvalidCommand(_)));
+    prop_assert!(invalid);
+  }
+}
+
+#[test]
+fn lowercases_only_keys() {
+  assert_eq!("POW".parse::<Command>().unwrap(), Command::from("pow"));
+  assert_eq!("Menu!".parse::<Command>().unwrap(), Command::Action("menu".into()));
+  assert_eq!("SOUR=HDMI2".parse::<Command>().unwrap(), Command::from(("sour", "HDMI2")));
+}
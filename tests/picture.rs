@@ -0,0 +1,86 @@
+use benq_control::{
+  AspectRatio, ColorTemperature, Error, Gamma, ModelProfile, Pacing, PictureLevel, PictureMode,
+  ProjectorControl
+};
+use benq_control::testing::{MockProjector, Reply};
+
+fn control(mock: MockProjector) -> benq_control::blocking::ProjectorControl {
+  ProjectorControl::builder()
+    .pacing(Pacing::none())
+    .build_blocking(mock)
+}
+
+#[test]
+fn parses_keywords() {
+  assert_eq!("CINE".parse::<PictureMode>().unwrap(), PictureMode::Cinema);
+  assert_eq!("cinema".parse::<PictureMode>().unwrap(), PictureMode::Cinema);
+  assert_eq!(PictureMode::Cinema.to_string(), "cine");
+  assert_eq!("isf".parse::<PictureMode>().unwrap(), PictureMode::Other("isf".into()));
+  assert!("".parse::<PictureMode>().is_err());
+
+  assert_eq!("16:9".parse::<AspectRatio>().unwrap(), AspectRatio::R16x9);
+  assert_eq!("2.2".parse::<Gamma>().unwrap(), Gamma::G22);
+
+  for mode in PictureMode::KNOWN {
+    assert_eq!(&mode.to_string().parse::<PictureMode>().unwrap(), mode);
+  }
+}
+
+#[test]
+fn queries_picture_settings() {
+  let mock = MockProjector::new()
+    .expect("appmod", Reply::response("APPMOD=CINE"))
+    .expect("bri", Reply::response("BRI=52"))
+    .expect("ct", Reply::response("CT=WARM"))
+    .expect("gamma", Reply::response("GAMMA=2.4"))
+    .expect("asp", Reply::response("ASP=AUTO"));
+  let handle = mock.handle();
+
+  let projector = control(mock);
+  assert_eq!(projector.picture_mode().unwrap(), PictureMode::Cinema);
+  assert_eq!(projector.picture_level(PictureLevel::Brightness).unwrap(), 52);
+  assert_eq!(projector.color_temperature().unwrap(), ColorTemperature::Warm);
+  assert_eq!(projector.gamma().unwrap(), Gamma::G24);
+  assert_eq!(projector.aspect_ratio().unwrap(), AspectRatio::Auto);
+
+  handle.assert_done();
+}
+
+#[test]
+fn validates_against_profile() {
+  let mock = MockProjector::new()
+    .expect("modelname", Reply::response("MODELNAME=TH685"))
+    .expect(("appmod", "game"), Reply::response("APPMOD=GAME"))
+    .expect(("sharp", "15"), Reply::response("SHARP=15"));
+  let handle = mock.handle();
+
+  let projector = control(mock);
+  assert_eq!(projector.detect_profile().unwrap().model, "TH685");
+
+  assert!(matches!(
+    projector.set_picture_mode(&PictureMode::Standard),
+    Err(Error::Unsupported { .. })
+  ));
+  projector.set_picture_mode(&PictureMode::Game).unwrap();
+
+  assert!(matches!(
+    projector.set_picture_level(PictureLevel::Sharpness, 16),
+    Err(Error::Unsupported { .. })
+  ));
+  projector.set_picture_level(PictureLevel::Sharpness, 15).unwrap();
+
+  handle.assert_done();
+}
+
+#[test]
+fn generic_profile_allows_unknown_values() {
+  let mock = MockProjector::new()
+    .expect(("appmod", "isf"), Reply::response("APPMOD=ISF"));
+  let handle = mock.handle();
+
+  let projector = control(mock);
+  assert_eq!(projector.profile(), ModelProfile::default());
+  projector.set_picture_mode(&PictureMode::Other("isf".into())).unwrap();
+
+  handle.assert_done();
+}
@@ -0,0 +1,71 @@
+use std::time::Duration;
+
+use benq_control::{Error, Pacing, PowerProgress, PowerState, PowerWait, ProjectorControl};
+use benq_control::testing::{MockProjector, Reply};
+
+fn control(mock: MockProjector) -> benq_control::blocking::ProjectorControl {
+  ProjectorControl::builder()
+    .pacing(Pacing::none())
+    .build_blocking(mock)
+}
+
+fn wait() -> PowerWait {
+  PowerWait {
+    timeout: Duration::from_secs(5),
+    poll_interval: Duration::from_millis(1),
+  }
+}
+
+#[test]
+fn waits_for_warm_up() {
+  let mock = MockProjector::new()
+    .expect("pow", Reply::response("POW=OFF"))
+    .expect(("pow", "on"), Reply::response("POW=ON"))
+    .expect("pow", Reply::BlockItem)
+    .expect("pow", Reply::response("POW=ON"))
+    .expect("sour", Reply::BlockItem)
+    .expect("pow", Reply::response("POW=ON"))
+    .expect("sour", Reply::response("SOUR=HDMI"));
+  let handle = mock.handle();
+
+  let mut events = Vec::new();
+  control(mock).power_on_and_wait(wait(), |p| events.push(p)).unwrap();
+  handle.assert_done();
+
+  assert_eq!(events.len(), 4);
+  assert_eq!(events[0], PowerProgress::Sent);
+  assert!(matches!(events[1], PowerProgress::Waiting { power: None, .. }));
+  assert!(matches!(events[2], PowerProgress::Waiting { power: Some(PowerState::On), .. }));
+  assert!(matches!(events[3], PowerProgress::Ready { .. }));
+}
+
+#[test]
+fn skips_command_in_target_state() {
+  let mock = MockProjector::new()
+    .expect("pow", Reply::response("POW=OFF"))
+    .expect("pow", Reply::response("POW=OFF"));
+  let handle = mock.handle();
+
+  let mut events = Vec::new();
+  control(mock).power_off_and_wait(wait(), |p| events.push(p)).unwrap();
+  handle.assert_done();
+
+  assert_eq!(events.len(), 2);
+  assert!(matches!(events[1], PowerProgress::Ready { .. }));
+}
+
+#[test]
+fn times_out() {
+  let mock = MockProjector::new()
+    .expect("pow", Reply::response("POW=ON"))
+    .expect(("pow", "off"), Reply::response("POW=OFF"));
+
+  let wait = PowerWait {
+    timeout: Duration::from_millis(50),
+    poll_interval: Duration::from_millis(10),
+  };
+
+  // every poll after this is refused
+  let result = control(mock).power_off_and_wait(wait, |_| ());
+  assert!(matches!(result, Err(Error::Timeout)));
+}
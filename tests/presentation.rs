@@ -0,0 +1,59 @@
+use benq_control::{Error, ModelProfile, Pacing, ProjectorControl, ThreeDMode};
+use benq_control::testing::{MockProjector, Reply};
+
+fn control(mock: MockProjector) -> benq_control::blocking::ProjectorControl {
+  ProjectorControl::builder()
+    .pacing(Pacing::none())
+    .build_blocking(mock)
+}
+
+#[test]
+fn blanks_and_freezes() {
+  let mock = MockProjector::new()
+    .expect(("blank", "on"), Reply::response("BLANK=ON"))
+    .expect("blank", Reply::response("BLANK=ON"))
+    .expect(("freeze", "off"), Reply::response("FREEZE=OFF"))
+    .expect("freeze", Reply::response("FREEZE=OFF"));
+  let handle = mock.handle();
+
+  let projector = control(mock);
+  projector.set_blank(true).unwrap();
+  assert!(projector.blanked().unwrap());
+  projector.set_freeze(false).unwrap();
+  assert!(!projector.frozen().unwrap());
+
+  handle.assert_done();
+}
+
+#[test]
+fn selects_3d_mode() {
+  let mock = MockProjector::new()
+    .expect(("3d", "sbs"), Reply::response("3D=SBS"))
+    .expect("3d", Reply::response("3D=SBS"));
+  let handle = mock.handle();
+
+  let projector = control(mock);
+  projector.set_three_d_mode(&ThreeDMode::SideBySide).unwrap();
+  assert_eq!(projector.three_d_mode().unwrap(), ThreeDMode::SideBySide);
+
+  handle.assert_done();
+}
+
+#[test]
+fn rejects_3d_on_models_without_it() {
+  let mock = MockProjector::new();
+  let handle = mock.handle();
+
+  let projector = control(mock);
+  projector.set_profile(ModelProfile {
+    three_d_modes: Some(Vec::new()),
+    ..ModelProfile::default()
+  });
+
+  assert!(matches!(
+    projector.set_three_d_mode(&ThreeDMode::Auto),
+    Err(Error::Unsupported { .. })
+  ));
+
+  handle.assert_done();
+}
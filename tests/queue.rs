@@ -0,0 +1,165 @@
+use benq_control::{Command, Error, Pacing, ProjectorControl, QueuePolicy};
+use benq_control::testing::{MockHandle, MockProjector, Reply};
+use futures::FutureExt;
+use futures::executor::block_on;
+
+mod common;
+use common::{wait_until, Gate};
+
+/// Starts a controller whose engine is held executing `pow` until the gate
+/// is opened. `mock` must expect `pow` first.
+fn busy(capacity: usize, policy: QueuePolicy, mock: MockProjector) -> (ProjectorControl, Gate, MockHandle) {
+  let handle = mock.handle();
+  let gate = Gate::default();
+
+  let projector = ProjectorControl::builder()
+    .pacing(Pacing::none())
+    .queue(capacity, policy)
+    .build(gate.wrap(mock));
+
+  drop(projector.submit_command("pow"));
+  wait_until(|| projector.queue_status().current.is_some());
+
+  (projector, gate, handle)
+}
+
+#[test]
+fn rejects_when_full() {
+  let mock = MockProjector::new()
+    .expect("pow", Reply::response("POW=ON"))
+    .expect("vol", Reply::response("VOL=5"))
+    .expect("mute", Reply::response("MUTE=OFF"));
+  let (projector, gate, handle) = busy(2, QueuePolicy::Reject, mock);
+
+  let vol = projector.submit_command("vol");
+  let mute = projector.submit_command("mute");
+  assert_eq!(projector.queue_status().depth, 2);
+
+  let rejected = projector.submit_command("sour").now_or_never();
+  assert!(matches!(
+    rejected,
+    Some(Err(Error::QueueFull { command: Command::Get(ref key) })) if key == "sour"
+  ));
+
+  // stopping always gets through
+  let stop = projector.clone().stop();
+  assert_eq!(projector.queue_status().depth, 3);
+
+  gate.open();
+  assert_eq!(block_on(vol).unwrap().as_deref(), Some("VOL=5"));
+  assert_eq!(block_on(mute).unwrap().as_deref(), Some("MUTE=OFF"));
+  assert_eq!(block_on(stop).unwrap(), None);
+  handle.assert_done();
+}
+
+#[test]
+fn waits_for_room() {
+  let mock = MockProjector::new()
+    .expect("pow", Reply::response("POW=ON"))
+    .expect("vol", Reply::response("VOL=5"))
+    .expect("mute", Reply::response("MUTE=OFF"));
+  let (projector, gate, handle) = busy(1, QueuePolicy::Wait, mock);
+
+  let vol = projector.submit_command("vol");
+  let mut mute = projector.submit_command("mute");
+
+  // not queued until there is room, however often it's polled
+  assert!((&mut mute).now_or_never().is_none());
+  assert!((&mut mute).now_or_never().is_none());
+  assert_eq!(projector.queue_status().depth, 1);
+
+  gate.open();
+  assert_eq!(block_on(mute).unwrap().as_deref(), Some("MUTE=OFF"));
+  assert_eq!(block_on(vol).unwrap().as_deref(), Some("VOL=5"));
+
+  handle.assert_done();
+  assert_eq!(handle.commands(), vec![
+    Command::from("pow"),
+    Command::from("vol"),
+    Command::from("mute"),
+  ]);
+}
+
+#[test]
+fn leaves_room_for_one_command() {
+  let mock = MockProjector::new()
+    .expect("pow", Reply::response("POW=ON"))
+    .expect("vol", Reply::response("VOL=5"));
+  let (projector, gate, handle) = busy(0, QueuePolicy::Wait, mock);
+
+  let vol = projector.submit_command("vol");
+  assert_eq!(projector.queue_status().capacity, 1);
+  assert_eq!(projector.queue_status().depth, 1);
+
+  gate.open();
+  assert_eq!(block_on(vol).unwrap().as_deref(), Some("VOL=5"));
+  handle.assert_done();
+}
+
+#[test]
+fn evicts_oldest_background_command() {
+  let mock = MockProjector::new()
+    .expect("pow", Reply::response("POW=ON"))
+    .expect("sour", Reply::response("SOUR=HDMI"))
+    .expect(("vol", "5"), Reply::response("VOL=5"));
+  let (projector, gate, handle) = busy(2, QueuePolicy::DropOldestBackground, mock);
+
+  let polled = projector.submit_background("vol");
+  let source = projector.submit_command("sour");
+  let volume = projector.submit_command(("vol", "5"));
+
+  // the background query made room and failed
+  assert!(matches!(
+    polled.now_or_never(),
+    Some(Err(Error::Evicted { command: Command::Get(ref key) })) if key == "vol"
+  ));
+
+  // with no background commands left, the next one is rejected
+  let rejected = projector.submit_command("mute").now_or_never();
+  assert!(matches!(rejected, Some(Err(Error::QueueFull { .. }))));
+  assert_eq!(projector.queue_status().depth, 2);
+
+  gate.open();
+  assert_eq!(block_on(source).unwrap().as_deref(), Some("SOUR=HDMI"));
+  assert_eq!(block_on(volume).unwrap().as_deref(), Some("VOL=5"));
+  handle.assert_done();
+}
+
+#[test]
+fn fails_commands_left_after_stopping() {
+  let mock = MockProjector::new().expect("pow", Reply::response("POW=ON"));
+  let (projector, gate, handle) = busy(8, QueuePolicy::Wait, mock);
+
+  let stop = projector.clone().stop();
+  let late = projector.submit_command("vol");
+  assert_eq!(projector.queue_status().depth, 2);
+
+  gate.open();
+  assert_eq!(block_on(stop).unwrap(), None);
+  assert!(matches!(
+    block_on(late),
+    Err(Error::CommandSendError { command: Command::Get(ref key) }) if key == "vol"
+  ));
+
+  // and nothing more can be queued
+  wait_until(|| gate.is_dropped());
+  assert!(matches!(block_on(projector.submit_command("vol")), Err(Error::CommandSendError { .. })));
+  handle.assert_done();
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serializes_status_during_internal_commands() {
+  let projector = ProjectorControl::builder()
+    .pacing(Pacing::none())
+    .build(MockProjector::new());
+
+  // the daemon queues sleeps itself, and reports them from `/queue`
+  drop(projector.submit_command(Command::Sleep(std::time::Duration::from_millis(200))));
+  wait_until(|| projector.queue_status().current.is_some());
+
+  let value = serde_json::to_value(projector.queue_status()).unwrap();
+  assert_eq!(value["current"]["command"], "(sleep 200ms)");
+  assert_eq!(value["depth"], 0);
+  assert!(value["paused_ms"].is_null());
+}
@@ -0,0 +1,144 @@
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use benq_control::{ConnectionState, Error, Pacing, ProjectorControl, ReconnectBackoff};
+use benq_control::testing::{MockProjector, Reply};
+use futures::executor::block_on;
+use futures::future;
+
+fn backoff() -> ReconnectBackoff {
+  ReconnectBackoff {
+    initial: Duration::from_millis(5),
+    max: Duration::from_millis(20),
+    max_attempts: None,
+  }
+}
+
+/// Polls `projector` until `f` accepts its connection state.
+fn wait_for_state(projector: &ProjectorControl, f: impl Fn(ConnectionState) -> bool) {
+  let deadline = Instant::now() + Duration::from_secs(5);
+  while !f(projector.connection_state()) {
+    assert!(Instant::now() < deadline, "stuck in {:?}", projector.connection_state());
+    thread::sleep(Duration::from_millis(1));
+  }
+}
+
+#[test]
+fn keeps_queued_commands_across_reconnects() {
+  let mock = MockProjector::new()
+    .expect("pow", Reply::response("POW=ON"))
+    .expect("sour", Reply::Disconnect)
+    .expect("sour", Reply::response("SOUR=HDMI"))
+    .expect("vol", Reply::response("VOL=5"))
+    .expect(("mute", "on"), Reply::response("MUTE=ON"));
+  let handle = mock.handle();
+
+  // the first attempt fails, then the second waits until the test is ready
+  let (go_tx, go_rx) = mpsc::channel::<()>();
+  let reopened = mock.clone();
+  let mut attempts = 0;
+
+  let projector = ProjectorControl::builder()
+    .pacing(Pacing::none())
+    .reconnect_backoff(backoff())
+    .reconnect(move || {
+      attempts += 1;
+      if attempts == 1 {
+        return Err(Error::Disconnected);
+      }
+
+      go_rx.recv().unwrap();
+      Ok(reopened.clone())
+    })
+    .build(mock);
+
+  let burst = vec![
+    projector.submit_command("pow"),
+    projector.submit_command("sour"),
+    projector.submit_command("vol"),
+    projector.submit_command(("mute", "on")),
+  ];
+
+  wait_for_state(&projector, |s| s == ConnectionState::Reconnecting { attempt: 1 });
+  assert_eq!(projector.queue_status().depth, 2);
+  go_tx.send(()).unwrap();
+
+  let results: Vec<_> = block_on(future::join_all(burst))
+    .into_iter()
+    .map(Result::unwrap)
+    .collect();
+  assert_eq!(results, vec![
+    Some("POW=ON".to_string()),
+    Some("SOUR=HDMI".to_string()),
+    Some("VOL=5".to_string()),
+    Some("MUTE=ON".to_string()),
+  ]);
+
+  assert_eq!(projector.connection_state(), ConnectionState::Connected);
+  handle.assert_done();
+}
+
+#[test]
+fn gives_up_after_max_attempts() {
+  let mock = MockProjector::new()
+    .expect("pow", Reply::Disconnect)
+    .expect("pow", Reply::response("POW=OFF"));
+  let handle = mock.handle();
+
+  // fail three times, then succeed for the next command
+  let attempts = Arc::new(Mutex::new(0));
+  let reopened = mock.clone();
+  let counter = Arc::clone(&attempts);
+
+  let projector = ProjectorControl::builder()
+    .pacing(Pacing::none())
+    .reconnect_backoff(ReconnectBackoff { max_attempts: Some(3), ..backoff() })
+    .reconnect(move || {
+      let mut attempts = counter.lock().unwrap();
+      *attempts += 1;
+      if *attempts <= 3 {
+        Err(Error::Disconnected)
+      } else {
+        Ok(reopened.clone())
+      }
+    })
+    .build_blocking(mock);
+
+  assert!(matches!(projector.submit_command("pow"), Err(Error::Disconnected)));
+  assert_eq!(projector.connection_state(), ConnectionState::Disconnected);
+  assert_eq!(*attempts.lock().unwrap(), 3);
+
+  // the next command tries again
+  assert_eq!(projector.submit_command("pow").unwrap().as_deref(), Some("POW=OFF"));
+  assert_eq!(projector.connection_state(), ConnectionState::Connected);
+  handle.assert_done();
+}
+
+#[test]
+fn shutdown_stops_reconnecting() {
+  let mock = MockProjector::new()
+    .expect("pow", Reply::Disconnect);
+
+  let projector = ProjectorControl::builder()
+    .pacing(Pacing::none())
+    .reconnect_backoff(backoff())
+    .reconnect(|| Err::<MockProjector, _>(Error::Disconnected))
+    .build(mock);
+
+  let pow = projector.submit_command("pow");
+  let queued = projector.submit_command("sour");
+  wait_for_state(&projector, |s| matches!(s, ConnectionState::Reconnecting { attempt } if attempt > 1));
+
+  // shut down on another thread so a hang fails the test rather than
+  // blocking it forever
+  let (done_tx, done_rx) = mpsc::channel();
+  thread::spawn(move || {
+    block_on(projector.shutdown());
+    done_tx.send(()).unwrap();
+  });
+
+  done_rx.recv_timeout(Duration::from_secs(5)).expect("shutdown hung while reconnecting");
+  assert!(matches!(block_on(pow), Err(Error::Disconnected)));
+  assert!(block_on(queued).is_err());
+}
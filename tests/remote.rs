@@ -0,0 +1,63 @@
+use std::time::{Duration, Instant};
+
+use benq_control::{Command, Error, Pacing, ProjectorControl, RemoteKey};
+use benq_control::testing::{MockProjector, Reply};
+
+#[test]
+fn presses_keys_in_order() {
+  let mock = MockProjector::new()
+    .expect(("menu", "on"), Reply::response("MENU=ON"))
+    .expect(&RemoteKey::Down, Reply::response("DOWN"))
+    .expect(&RemoteKey::Down, Reply::response("DOWN"))
+    .expect(&RemoteKey::Enter, Reply::response("ENTER"))
+    .expect(("menu", "off"), Reply::response("MENU=OFF"));
+  let handle = mock.handle();
+
+  let projector = ProjectorControl::builder()
+    .pacing(Pacing::none())
+    .build_blocking(mock);
+
+  projector.press_sequence(&[
+    RemoteKey::Menu,
+    RemoteKey::Down,
+    RemoteKey::Down,
+    RemoteKey::Enter,
+    RemoteKey::Exit,
+  ]).unwrap();
+
+  handle.assert_done();
+  assert_eq!(handle.commands()[1], Command::Action("down".into()));
+}
+
+#[test]
+fn paces_key_presses() {
+  let mock = MockProjector::new()
+    .expect(&RemoteKey::Up, Reply::response("UP"))
+    .expect(&RemoteKey::Up, Reply::response("UP"))
+    .expect(&RemoteKey::Up, Reply::response("UP"));
+
+  let projector = ProjectorControl::builder()
+    .pacing(Pacing { key_press: Duration::from_millis(100), ..Pacing::none() })
+    .build_blocking(mock);
+
+  let started = Instant::now();
+  projector.press_sequence(&[RemoteKey::Up, RemoteKey::Up, RemoteKey::Up]).unwrap();
+  assert!(started.elapsed() >= Duration::from_millis(200));
+}
+
+#[test]
+fn stops_at_first_failure() {
+  let mock = MockProjector::new()
+    .expect(&RemoteKey::Left, Reply::BlockItem);
+  let handle = mock.handle();
+
+  let projector = ProjectorControl::builder()
+    .pacing(Pacing::none())
+    .build_blocking(mock);
+
+  let result = projector.press_sequence(&[RemoteKey::Left, RemoteKey::Right]);
+  assert!(matches!(result, Err(Error::ResponseBlockItem)));
+
+  handle.assert_done();
+  assert_eq!(handle.commands().len(), 1);
+}
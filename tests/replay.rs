@@ -0,0 +1,116 @@
+use std::io::{self, Read, Write};
+
+use benq_control::{Error, Result};
+use benq_control::protocol::{self, ProtocolOptions, Transport};
+use benq_control::transcript::{Direction, Recorder, Replay, Transcript};
+use serialport::ClearBuffer;
+
+const TH685: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/transcripts/th685.txt");
+
+/// A well-behaved fake projector that answers every query with `response`.
+struct Projector {
+  response: &'static str,
+  written: Vec<u8>,
+  pending: Vec<u8>,
+}
+
+impl Read for Projector {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    if self.pending.is_empty() {
+      return Err(io::ErrorKind::TimedOut.into());
+    }
+
+    let n = buf.len().min(self.pending.len());
+    buf[..n].copy_from_slice(&self.pending[..n]);
+    self.pending.drain(..n);
+    Ok(n)
+  }
+}
+
+impl Write for Projector {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    for &b in buf {
+      self.written.push(b);
+      if b != b'\r' {
+        continue;
+      }
+
+      let line = std::mem::take(&mut self.written);
+      if line == b"\r" {
+        self.pending.extend(b">");
+      } else {
+        self.pending.extend(&line[..line.len() - 1]);
+        self.pending.extend(format!("\r\n*{}#\r\n", self.response).as_bytes());
+      }
+    }
+
+    Ok(buf.len())
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    Ok(())
+  }
+}
+
+impl Transport for Projector {
+  fn clear(&mut self, _buffer: ClearBuffer) -> Result<()> {
+    Ok(())
+  }
+}
+
+#[test]
+fn replays_recorded_session() {
+  let options = ProtocolOptions::default();
+  let mut port = Replay::load(TH685).unwrap();
+
+  let res = protocol::send_get(&mut port, "pow", &options);
+  assert_eq!(res.unwrap().as_deref(), Some("POW=ON"));
+
+  let res = protocol::send_get(&mut port, "sour", &options);
+  assert_eq!(res.unwrap().as_deref(), Some("SOUR=HDMI"));
+
+  let res = protocol::send_get(&mut port, "vol", &options);
+  assert!(matches!(res, Err(Error::ResponseBlockItem)), "{:?}", res);
+
+  assert!(port.is_finished());
+}
+
+#[test]
+fn replays_in_realtime() {
+  let mut port = Replay::load(TH685).unwrap().realtime(true);
+
+  let res = protocol::send_get(&mut port, "pow", &ProtocolOptions::default());
+  assert_eq!(res.unwrap().as_deref(), Some("POW=ON"));
+}
+
+#[test]
+fn rejects_unexpected_commands() {
+  let mut port = Replay::load(TH685).unwrap();
+
+  let res = protocol::send_get(&mut port, "lampm", &ProtocolOptions::default());
+  assert!(
+    matches!(&res, Err(Error::SerialIOError { source }) if source.kind() == io::ErrorKind::InvalidData),
+    "{:?}", res
+  );
+}
+
+#[test]
+fn recording_round_trips() {
+  let options = ProtocolOptions::default();
+  let projector = Projector { response: "MUTE=OFF", written: Vec::new(), pending: Vec::new() };
+  let mut recorder = Recorder::new(projector, Vec::new());
+
+  let recorded = protocol::send_get(&mut recorder, "mute", &options).unwrap();
+  assert_eq!(recorded.as_deref(), Some("MUTE=OFF"));
+
+  let (_, sink) = recorder.into_inner();
+  let transcript: Transcript = String::from_utf8(sink).unwrap().parse().unwrap();
+
+  assert_eq!(transcript.events.first().map(|e| e.direction), Some(Direction::Write));
+  assert_eq!(transcript.to_string().parse::<Transcript>().unwrap(), transcript);
+
+  let mut port = Replay::new(transcript);
+  let replayed = protocol::send_get(&mut port, "mute", &options).unwrap();
+  assert_eq!(replayed, recorded);
+  assert!(port.is_finished());
+}
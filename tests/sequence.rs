@@ -0,0 +1,107 @@
+use std::time::Duration;
+
+use benq_control::{Command, Error, Pacing, PowerState, ProjectorControl};
+use benq_control::sequence::{OnError, Sequence, StepOutcome};
+use benq_control::testing::{MockProjector, Reply};
+
+fn control(mock: MockProjector) -> benq_control::blocking::ProjectorControl {
+  ProjectorControl::builder()
+    .pacing(Pacing::none())
+    .build_blocking(mock)
+}
+
+fn movie_night() -> Sequence {
+  Sequence::new()
+    .when("pow", "off", Sequence::new().power(PowerState::On))
+    .command(("sour", "hdmi2"))
+    .wait(Duration::from_millis(1))
+    .command(("vol", "8"))
+}
+
+#[test]
+fn runs_steps_in_order() {
+  let mock = MockProjector::new()
+    .expect("pow", Reply::response("POW=OFF"))
+    .expect("pow", Reply::response("POW=OFF"))
+    .expect(("pow", "on"), Reply::response("POW=ON"))
+    .expect("pow", Reply::response("POW=ON"))
+    .expect("sour", Reply::response("SOUR=HDMI"))
+    .expect(("sour", "hdmi2"), Reply::response("SOUR=HDMI2"))
+    .expect(("vol", "8"), Reply::response("VOL=8"));
+  let handle = mock.handle();
+
+  let report = control(mock).run_sequence(&movie_night()).unwrap();
+  handle.assert_done();
+
+  assert!(report.is_ok());
+  let steps: Vec<String> = report.steps.iter().map(ToString::to_string).collect();
+  assert_eq!(steps, vec![
+    "when pow=off: ok (OFF)",
+    "  power on: ok",
+    "sour=hdmi2: ok (SOUR=HDMI2)",
+    "wait 1ms: ok",
+    "vol=8: ok (VOL=8)",
+  ]);
+}
+
+#[test]
+fn skips_unmet_conditions() {
+  let mock = MockProjector::new()
+    .expect("pow", Reply::response("POW=ON"))
+    .expect(("sour", "hdmi2"), Reply::response("SOUR=HDMI2"))
+    .expect(("vol", "8"), Reply::response("VOL=8"));
+  let handle = mock.handle();
+
+  let report = control(mock).run_sequence(&movie_night()).unwrap();
+  handle.assert_done();
+
+  assert!(matches!(report.steps[0].outcome, StepOutcome::Skipped));
+  assert_eq!(report.steps.len(), 4);
+}
+
+#[test]
+fn stops_or_continues_on_error() {
+  let sequence = Sequence::new()
+    .command(("sour", "hdmi2"))
+    .command(("vol", "8"));
+
+  let mock = MockProjector::new()
+    .expect(("sour", "hdmi2"), Reply::BlockItem);
+  let handle = mock.handle();
+
+  let report = control(mock).run_sequence(&sequence).unwrap();
+  handle.assert_done();
+  assert!(report.stopped);
+  assert_eq!(report.steps.len(), 1);
+  assert!(matches!(report.steps[0].outcome, StepOutcome::Failed(Error::ResponseBlockItem)));
+
+  let mock = MockProjector::new()
+    .expect(("sour", "hdmi2"), Reply::BlockItem)
+    .expect(("vol", "8"), Reply::response("VOL=8"));
+  let handle = mock.handle();
+
+  let report = control(mock).run_sequence(&sequence.on_error(OnError::Continue)).unwrap();
+  handle.assert_done();
+  assert!(!report.stopped);
+  assert!(!report.is_ok());
+  assert_eq!(handle.commands().last(), Some(&Command::from(("vol", "8"))));
+}
+
+#[test]
+fn rejects_internal_commands() {
+  let sequence = Sequence::new()
+    .command(Command::Stop)
+    .command(Command::Sleep(Duration::from_millis(1)))
+    .command(("vol", "8"))
+    .on_error(OnError::Continue);
+
+  let mock = MockProjector::new()
+    .expect(("vol", "8"), Reply::response("VOL=8"));
+  let handle = mock.handle();
+
+  let report = control(mock).run_sequence(&sequence).unwrap();
+  handle.assert_done();
+  assert!(matches!(report.steps[0].outcome, StepOutcome::Failed(Error::InvalidCommand(_))));
+  assert!(matches!(report.steps[1].outcome, StepOutcome::Failed(Error::InvalidCommand(_))));
+  assert!(matches!(report.steps[2].outcome, StepOutcome::Done(_)));
+}
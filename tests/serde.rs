@@ -0,0 +1,113 @@
+use std::time::Duration;
+
+use benq_control::{
+  Command, CommandOutcome, Error, ErrorInfo, LampMode, MaintenanceStatus, PictureMode, PowerState,
+  Snapshot, Source
+};
+use benq_control::sequence::{Sequence, Step};
+use serde_json::json;
+
+#[test]
+fn commands_use_command_syntax() {
+  let commands = vec![
+    Command::from("pow"),
+    Command::from(("sour", "hdmi2")),
+    Command::Action("left".to_string()),
+  ];
+
+  let value = serde_json::to_value(&commands).unwrap();
+  assert_eq!(value, json!(["pow=?", "sour=hdmi2", "left!"]));
+  assert_eq!(serde_json::from_value::<Vec<Command>>(value).unwrap(), commands);
+
+  assert!(serde_json::from_value::<Command>(json!("pow=on#")).is_err());
+}
+
+#[test]
+fn every_command_round_trips_or_fails_to_serialize() {
+  let commands = vec![
+    Command::Get("pow".to_string()),
+    Command::Set(("sour".to_string(), "HDMI2".to_string())),
+    Command::Action("menu".to_string()),
+    Command::Stop,
+    Command::Sleep(Duration::from_secs(1)),
+  ];
+
+  for command in commands {
+    match command {
+      Command::Get(_) | Command::Set(_) | Command::Action(_) => {
+        let value = serde_json::to_value(&command).unwrap();
+        assert_eq!(serde_json::from_value::<Command>(value).unwrap(), command);
+      },
+      Command::Stop | Command::Sleep(_) => {
+        assert!(serde_json::to_value(&command).is_err(), "{:?} serialized", command);
+      },
+    }
+  }
+}
+
+#[test]
+fn values_use_projector_names() {
+  assert_eq!(serde_json::to_value(PowerState::On).unwrap(), json!("on"));
+  assert_eq!(serde_json::to_value(Source::Component).unwrap(), json!("ypbr"));
+  assert_eq!(serde_json::to_value(PictureMode::Cinema).unwrap(), json!("cine"));
+
+  assert_eq!(serde_json::from_value::<PictureMode>(json!("CINEMA")).unwrap(), PictureMode::Cinema);
+  assert_eq!(
+    serde_json::from_value::<Source>(json!("hdmi3")).unwrap(),
+    Source::Other("hdmi3".to_string())
+  );
+
+  let status = MaintenanceStatus {
+    lamp_mode: LampMode::Eco,
+    lamp_hours: vec![1200],
+    filter_hours: None,
+  };
+  let value = serde_json::to_value(&status).unwrap();
+  assert_eq!(value, json!({"lamp_mode": "eco", "lamp_hours": [1200], "filter_hours": null}));
+  assert_eq!(serde_json::from_value::<MaintenanceStatus>(value).unwrap(), status);
+}
+
+#[test]
+fn errors_have_stable_codes() {
+  assert_eq!(
+    serde_json::to_value(Error::ResponseBlockItem).unwrap(),
+    json!({"code": "block_item", "message": "projector returned an error ('Block item')"})
+  );
+
+  let outcomes = vec![
+    CommandOutcome::from(Ok(Some("POW=ON".to_string()))),
+    CommandOutcome::from(Err(Error::Timeout)),
+  ];
+  let value = serde_json::to_value(&outcomes).unwrap();
+  assert_eq!(value, json!([
+    {"response": "POW=ON"},
+    {"error": {"code": "timeout", "message": "timed out waiting for the projector"}},
+  ]));
+
+  let outcomes: Vec<CommandOutcome> = serde_json::from_value(value).unwrap();
+  assert!(matches!(&outcomes[1], CommandOutcome::Error(ErrorInfo { code, .. }) if code == "timeout"));
+}
+
+#[test]
+fn sequences_and_snapshots_round_trip() {
+  let sequence = Sequence::new()
+    .when("pow", "off", Sequence::new().power(PowerState::On))
+    .command(("sour", "hdmi2"))
+    .wait(Duration::from_millis(1500));
+
+  let value = serde_json::to_value(&sequence).unwrap();
+  assert_eq!(value["steps"][0]["when"]["steps"], json!([{"power": "on"}]));
+  assert_eq!(value["steps"][1], json!({"command": "sour=hdmi2"}));
+  assert_eq!(value["steps"][2], json!({"wait": 1.5}));
+  assert_eq!(serde_json::from_value::<Sequence>(value).unwrap(), sequence);
+
+  assert!(serde_json::from_value::<Step>(json!({"wait": -1})).is_err());
+
+  let snapshot = Snapshot {
+    model: "TH685".to_string(),
+    settings: vec![("bri".to_string(), "50".to_string())].into_iter().collect(),
+  };
+  let value = serde_json::to_value(&snapshot).unwrap();
+  assert_eq!(value, json!({"model": "TH685", "settings": {"bri": "50"}}));
+  assert_eq!(serde_json::from_value::<Snapshot>(value).unwrap(), snapshot);
+}
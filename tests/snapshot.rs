@@ -0,0 +1,125 @@
+use benq_control::{Command, Error, ModelProfile, Pacing, ProjectorControl, Snapshot};
+use benq_control::testing::{MockProjector, Reply};
+
+fn control(mock: MockProjector) -> benq_control::blocking::ProjectorControl {
+  ProjectorControl::builder()
+    .pacing(Pacing::none())
+    .build_blocking(mock)
+}
+
+fn snapshot(settings: &[(&str, &str)]) -> Snapshot {
+  Snapshot {
+    model: "TH685".to_string(),
+    settings: settings.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+  }
+}
+
+#[test]
+fn reads_profile_settings() {
+  let profile = ModelProfile {
+    lamp_modes: Some(Vec::new()),
+    three_d_modes: Some(Vec::new()),
+    vertical_keystone: None,
+    horizontal_keystone: None,
+    ..ModelProfile::generic("W1070")
+  };
+  assert_eq!(profile.settings(), vec![
+    "appmod", "bri", "con", "color", "sharp", "ct", "gamma", "asp", "pp", "lang", "vol"
+  ]);
+
+  let mut mock = MockProjector::new();
+  for key in profile.settings() {
+    let reply = match key {
+      "gamma" => Reply::BlockItem,
+      "appmod" => Reply::response("APPMOD=CINE"),
+      _ => Reply::response(format!("{}=1", key.to_uppercase())),
+    };
+    mock = mock.expect(key, reply);
+  }
+  let handle = mock.handle();
+
+  let projector = control(mock);
+  projector.set_profile(profile);
+
+  let snapshot = projector.snapshot().unwrap();
+  assert_eq!(snapshot.model, "W1070");
+  assert_eq!(snapshot.settings.get("appmod").map(String::as_str), Some("cine"));
+  assert_eq!(snapshot.settings.get("vol").map(String::as_str), Some("1"));
+  assert!(!snapshot.settings.contains_key("gamma"));
+  assert_eq!(snapshot.settings.len(), 10);
+
+  handle.assert_done();
+}
+
+#[test]
+fn restores_in_profile_order() {
+  let mock = MockProjector::new()
+    .expect(("appmod", "cine"), Reply::response("APPMOD=CINE"))
+    .expect(("bri", "55"), Reply::response("BRI=55"))
+    .expect(("vol", "3"), Reply::BlockItem)
+    .expect(("zoom", "2"), Reply::response("ZOOM=2"));
+  let handle = mock.handle();
+
+  let projector = control(mock);
+  let report = projector.restore(&snapshot(&[
+    ("zoom", "2"),
+    ("vol", "3"),
+    ("bri", "55"),
+    ("appmod", "cine"),
+  ])).unwrap();
+
+  assert_eq!(report.applied, vec!["appmod", "bri", "zoom"]);
+  assert_eq!(report.failed.len(), 1);
+  assert_eq!(report.failed[0].0, "vol");
+  assert!(matches!(report.failed[0].1, Error::ResponseBlockItem));
+  assert!(!report.is_ok());
+
+  handle.assert_done();
+  assert_eq!(handle.commands()[0], Command::from(("appmod", "cine")));
+}
+
+#[test]
+fn skips_values_the_profile_rejects() {
+  let mock = MockProjector::new()
+    .expect(("con", "40"), Reply::response("CON=40"))
+    .expect(("vkeystone", "-5"), Reply::response("VKEYSTONE=-5"));
+  let handle = mock.handle();
+
+  let projector = control(mock);
+  projector.set_profile(ModelProfile::th685());
+  let report = projector.restore(&snapshot(&[
+    ("appmod", "dynamic"),
+    ("con", "40"),
+    ("sharp", "20"),
+    ("bri", "bright"),
+    ("vkeystone", "-5"),
+    ("hkeystone", "3"),
+  ])).unwrap();
+
+  handle.assert_done();
+  assert_eq!(report.applied, vec!["con", "vkeystone"]);
+
+  let failed: Vec<&str> = report.failed.iter().map(|(key, _)| key.as_str()).collect();
+  assert_eq!(failed, vec!["appmod", "bri", "sharp", "hkeystone"]);
+  for (key, error) in &report.failed {
+    match key.as_str() {
+      "bri" => assert!(matches!(error, Error::InvalidValue(_)), "{:?}", error),
+      _ => assert!(matches!(error, Error::Unsupported { .. }), "{}: {:?}", key, error),
+    }
+  }
+}
+
+#[test]
+fn diffs_snapshots() {
+  let left = snapshot(&[("bri", "50"), ("ct", "warm"), ("vol", "3")]);
+  let right = snapshot(&[("bri", "50"), ("ct", "COOL"), ("lang", "english")]);
+
+  let diff: Vec<String> = left.diff(&right).iter().map(ToString::to_string).collect();
+  assert_eq!(diff, vec![
+    "ct: warm -> COOL",
+    "lang: (unset) -> english",
+    "vol: 3 -> (unset)",
+  ]);
+
+  assert!(left.diff(&left).is_empty());
+}